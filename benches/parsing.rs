@@ -0,0 +1,90 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use desktopentries::checker::Checker;
+use desktopentries::configuration::Configuration;
+use desktopentries::entry::Entry;
+use structopt::StructOpt;
+
+static ENTRY_COUNT: usize = 2000;
+static LANGUAGES: &[&str] = &[
+    "cs", "de", "es", "fr", "it", "ja", "nl", "pl", "pt", "ru", "zh_CN",
+];
+
+/// Generates entries resembling the ones installed by desktop environments, with translations
+/// and actions. Every fourth entry is a Link, the rest are Applications.
+fn generate_entries() -> Vec<String> {
+    (0..ENTRY_COUNT)
+        .map(|index| {
+            let mut contents = String::from("[Desktop Entry]\n");
+            if index % 4 == 0 {
+                contents.push_str("Type=Link\n");
+            } else {
+                contents.push_str("Type=Application\n");
+            }
+            contents.push_str(&format!("Name=Entry {}\n", index));
+            for language in LANGUAGES {
+                contents.push_str(&format!(
+                    "Name[{}]=Entry {} {}\n",
+                    language, index, language
+                ));
+            }
+            contents.push_str("GenericName=Generic Entry\n");
+            for language in LANGUAGES {
+                contents.push_str(&format!(
+                    "Comment[{}]=A comment in {}\n",
+                    language, language
+                ));
+            }
+            contents.push_str(&format!("Exec=/usr/bin/entry-{} %U\n", index));
+            contents.push_str(&format!("Icon=entry-{}\n", index));
+            contents.push_str("Categories=Utility;Development;\n");
+            contents.push_str("MimeType=text/plain;text/x-csrc;text/x-chdr;\n");
+            contents.push_str("Keywords=entry;test;benchmark;\n");
+            contents.push_str("Actions=new-window;\n\n");
+            contents.push_str("[Desktop Action new-window]\nName=New Window\n");
+            contents.push_str(&format!("Exec=/usr/bin/entry-{} --new-window\n", index));
+            contents
+        })
+        .collect()
+}
+
+fn bench_query(c: &mut Criterion, name: &str, args: &[&str], extra_keys: &[&str]) {
+    let entries = generate_entries();
+    let mut full_args = vec!["desktopentries"];
+    full_args.extend(args);
+    let checker = Checker::new(Configuration::from_iter(&full_args));
+
+    let mut group = c.benchmark_group(name);
+    group.bench_function("full", |b| {
+        b.iter(|| {
+            entries
+                .iter()
+                .filter_map(|contents| Entry::new(black_box(contents)).ok())
+                .filter(|entry| checker.check_entry(entry))
+                .count()
+        })
+    });
+    group.bench_function("lazy", |b| {
+        b.iter(|| {
+            entries
+                .iter()
+                .filter_map(|contents| {
+                    checker
+                        .parse_entry(black_box(contents), Some(extra_keys))
+                        .ok()
+                        .flatten()
+                })
+                .count()
+        })
+    });
+    group.finish();
+}
+
+fn bench_parsing(c: &mut Criterion) {
+    bench_query(c, "all", &[], &[]);
+    bench_query(c, "not_application", &["-A"], &[]);
+    bench_query(c, "application_name", &["-a", "-n", "Entry 1"], &[]);
+    bench_query(c, "menu", &["-a"], &["Name", "GenericName", "Icon"]);
+}
+
+criterion_group!(benches, bench_parsing);
+criterion_main!(benches);
@@ -0,0 +1,196 @@
+use std::fs;
+use std::path::PathBuf;
+
+use super::discovery;
+use super::entry::Entry;
+use super::exec;
+use super::output::MatchedEntry;
+use super::xdg;
+
+/// The keys deciding whether an entry is started at login.
+pub static KEYS: &[&str] = &[
+    "Type",
+    "Hidden",
+    "OnlyShowIn",
+    "NotShowIn",
+    "TryExec",
+    GNOME_ENABLED_KEY,
+];
+/// The key used by GNOME to disable autostart entries.
+pub static GNOME_ENABLED_KEY: &str = "X-GNOME-Autostart-enabled";
+
+/// Returns the autostart directories, $XDG_CONFIG_HOME/autostart followed by the autostart
+/// subdirectories of $XDG_CONFIG_DIRS, in the order of their precedence.
+pub fn autostart_dirs() -> Vec<PathBuf> {
+    let mut dirs: Vec<PathBuf> = Vec::new();
+    for dir in xdg::config_dirs() {
+        let dir = dir.join("autostart");
+        if !dirs.contains(&dir) {
+            dirs.push(dir);
+        }
+    }
+    dirs
+}
+
+/// Returns the user's autostart directory, $XDG_CONFIG_HOME/autostart.
+pub fn user_autostart_dir() -> Option<PathBuf> {
+    xdg::config_home().map(|dir| dir.join("autostart"))
+}
+
+/// Returns why the entry is not started at login in the desktop environments, or none if it is
+/// started, as described by the Desktop Application Autostart Specification.
+pub fn skip_reason(entry: &Entry, desktops: &[String]) -> Option<String> {
+    let is_true = |key: &str| entry.get(key).is_some_and(|value| value == "true");
+    let list = |key: &str| -> Vec<&str> {
+        entry
+            .get(key)
+            .map(|value| value.split(';').filter(|item| !item.is_empty()).collect())
+            .unwrap_or_default()
+    };
+
+    if let Some(entry_type) = entry.get("Type") {
+        if entry_type != "Application" {
+            return Some(format!("Type is {}", entry_type));
+        }
+    }
+    if is_true("Hidden") {
+        return Some(String::from("Hidden is set to true"));
+    }
+    if entry
+        .get(GNOME_ENABLED_KEY)
+        .is_some_and(|value| value == "false")
+    {
+        return Some(format!("{} is set to false", GNOME_ENABLED_KEY));
+    }
+
+    let only_show_in = list("OnlyShowIn");
+    if !only_show_in.is_empty()
+        && !desktops
+            .iter()
+            .any(|desktop| only_show_in.contains(&desktop.as_str()))
+    {
+        return Some(format!("OnlyShowIn is {}", only_show_in.join(";")));
+    }
+    let not_show_in = list("NotShowIn");
+    if let Some(desktop) = desktops
+        .iter()
+        .find(|desktop| not_show_in.contains(&desktop.as_str()))
+    {
+        return Some(format!("NotShowIn contains {}", desktop));
+    }
+
+    if let Some(try_exec) = entry.get("TryExec") {
+        if exec::find_executable(try_exec).is_none() {
+            return Some(format!("TryExec {} not found", try_exec));
+        }
+    }
+    None
+}
+
+/// Returns whether the entry is started at login in the desktop environments.
+pub fn will_start(entry: &Entry, desktops: &[String]) -> bool {
+    skip_reason(entry, desktops).is_none()
+}
+
+/// Loads the entries of the autostart directories which are started at login in the desktop
+/// environments, in the order of their IDs, which is the order they are started in. The errors
+/// are the messages of the entries which could not be read or parsed.
+pub fn effective_entries(
+    dirs: &[PathBuf],
+    desktops: &[String],
+    jobs: usize,
+) -> (Vec<MatchedEntry>, Vec<String>) {
+    let mut sources: Vec<(String, PathBuf)> = discovery::get_entries_from_dirs(dirs, jobs)
+        .into_iter()
+        .collect();
+    sources.sort();
+
+    let mut entries = Vec::new();
+    let mut errors = Vec::new();
+    for (id, path) in sources {
+        let parsed = fs::read_to_string(&path)
+            .map_err(|error| error.to_string())
+            .and_then(|contents| match Entry::new(&contents) {
+                Ok(entry) => Ok((contents, entry)),
+                Err(error) => Err(error.to_string()),
+            });
+        match parsed {
+            Ok((contents, entry)) if will_start(&entry, desktops) => entries.push(MatchedEntry {
+                id,
+                path,
+                contents,
+                entry,
+                score: None,
+            }),
+            Ok(_) => {}
+            Err(error) => errors.push(format!("{} in {}", error, path.display())),
+        }
+    }
+    (entries, errors)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tempdir::TempDir;
+
+    fn entry(keys: &str) -> Entry {
+        Entry::new(&format!(
+            "[Desktop Entry]\nType=Application\nName=Foo\nExec=foo\n{}",
+            keys
+        ))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_skip_reason() {
+        let desktops = vec![String::from("XFCE")];
+
+        assert_eq!(skip_reason(&entry(""), &desktops), None);
+        assert_eq!(
+            skip_reason(&entry("OnlyShowIn=GNOME;XFCE;"), &desktops),
+            None
+        );
+        assert_eq!(
+            skip_reason(&entry("Hidden=true"), &desktops).as_deref(),
+            Some("Hidden is set to true")
+        );
+        assert!(!will_start(
+            &entry("X-GNOME-Autostart-enabled=false"),
+            &desktops
+        ));
+        assert!(!will_start(&entry("OnlyShowIn=GNOME;"), &desktops));
+        assert!(!will_start(&entry("OnlyShowIn=GNOME;"), &[]));
+        assert!(!will_start(&entry("NotShowIn=KDE;XFCE;"), &desktops));
+        assert!(!will_start(&entry("TryExec=/nonexistent/foo"), &desktops));
+        assert!(will_start(&entry("TryExec=sh"), &desktops));
+    }
+
+    #[test]
+    fn test_effective_entries() -> std::io::Result<()> {
+        let root = TempDir::new("autostart");
+        let user_dir = root.join("user");
+        let system_dir = root.join("system");
+        fs::create_dir_all(&user_dir)?;
+        fs::create_dir_all(&system_dir)?;
+        let contents = "[Desktop Entry]\nType=Application\nName=Foo\nExec=foo\n";
+        fs::write(system_dir.join("b.desktop"), contents)?;
+        fs::write(system_dir.join("a.desktop"), contents)?;
+        fs::write(system_dir.join("c.desktop"), contents)?;
+        fs::write(
+            user_dir.join("c.desktop"),
+            format!("{}Hidden=true\n", contents),
+        )?;
+        fs::write(
+            user_dir.join("d.desktop"),
+            "[Desktop Entry]\ninvalid line\n",
+        )?;
+
+        let (entries, errors) = effective_entries(&[user_dir, system_dir], &[], 1);
+        let ids: Vec<&str> = entries.iter().map(|matched| matched.id.as_str()).collect();
+        assert_eq!(ids, vec!["a.desktop", "b.desktop"]);
+        assert_eq!(errors.len(), 1);
+
+        Ok(())
+    }
+}
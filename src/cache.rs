@@ -0,0 +1,426 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::time::UNIX_EPOCH;
+
+use super::checker::Checker;
+use super::discovery::{self, EntryKind};
+use super::entry::Entry;
+use super::output::MatchedEntry;
+use super::parallel;
+use super::xdg;
+
+static INDEX_VERSION: u32 = 2;
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+struct Mtime {
+    secs: u64,
+    nanos: u32,
+}
+
+impl Mtime {
+    fn of(path: &Path) -> Option<(Mtime, u64)> {
+        let metadata = fs::metadata(path).ok()?;
+        let duration = metadata.modified().ok()?.duration_since(UNIX_EPOCH).ok()?;
+        Some((
+            Mtime {
+                secs: duration.as_secs(),
+                nanos: duration.subsec_nanos(),
+            },
+            metadata.len(),
+        ))
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct IndexedDir {
+    path: PathBuf,
+    /// The modification times of the directory and all of its subdirectories. Adding, removing
+    /// or renaming a file changes the modification time of its parent directory, so the
+    /// directory has to be scanned again only if any of these change.
+    dir_mtimes: Vec<(PathBuf, Option<Mtime>)>,
+    files: Vec<IndexedFile>,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct IndexedFile {
+    pub id: String,
+    pub path: PathBuf,
+    mtime: Mtime,
+    size: u64,
+    /// The parsed entry, or the parse error message. The contents are not stored, as the parsed
+    /// keys are all most queries need.
+    pub entry: Result<Entry, String>,
+}
+
+/// An on-disk index of the entries found in the applications directories. Directories whose
+/// subdirectories did not change are not scanned again, files whose modification time and size
+/// did not change are not read and parsed again. Only the directories of the last refresh are
+/// kept.
+#[derive(Serialize, Deserialize)]
+pub struct Index {
+    version: u32,
+    dirs: Vec<IndexedDir>,
+    #[serde(skip)]
+    path: Option<PathBuf>,
+    #[serde(skip)]
+    changed: bool,
+}
+
+impl Index {
+    pub fn new() -> Index {
+        Index {
+            version: INDEX_VERSION,
+            dirs: Vec::new(),
+            path: None,
+            changed: false,
+        }
+    }
+
+    pub fn default_path() -> Option<PathBuf> {
+        xdg::cache_home().map(|mut path| {
+            path.push("desktopentries");
+            path.push("index.json");
+            path
+        })
+    }
+
+    /// Loads the index stored in $XDG_CACHE_HOME/desktopentries/index.json.
+    pub fn load_default() -> Index {
+        match Index::default_path() {
+            Some(path) => Index::load(&path),
+            None => Index::new(),
+        }
+    }
+
+    /// Loads the index from the path, starting with an empty index if the file does not exist or
+    /// was written by an incompatible version.
+    pub fn load(path: &Path) -> Index {
+        let index = fs::read(path)
+            .ok()
+            .and_then(|contents| serde_json::from_slice::<Index>(&contents).ok())
+            .filter(|index| index.version == INDEX_VERSION);
+
+        let mut index = index.unwrap_or_default();
+        index.path = Some(path.to_path_buf());
+        index
+    }
+
+    /// Brings the indexed directories up to date, using the specified number of threads. Returns
+    /// the number of files which were read and parsed again.
+    pub fn refresh(&mut self, dirs: &[PathBuf], jobs: usize) -> usize {
+        let mut indexed_dirs: HashMap<PathBuf, IndexedDir> = self
+            .dirs
+            .drain(..)
+            .map(|indexed_dir| (indexed_dir.path.clone(), indexed_dir))
+            .collect();
+
+        let mut refreshed = 0;
+        for dir in dirs {
+            if self.dirs.iter().any(|indexed_dir| &indexed_dir.path == dir) {
+                continue;
+            }
+            let cached = indexed_dirs.remove(dir);
+            let (indexed_dir, count, rescanned) = refresh_dir(dir, cached, jobs);
+            refreshed += count;
+            if rescanned {
+                self.changed = true;
+            }
+            self.dirs.push(indexed_dir);
+        }
+
+        // The directories which were not requested are dropped, so that the index does not keep
+        // growing when the data directories change.
+        if !indexed_dirs.is_empty() {
+            self.changed = true;
+        }
+        self.dirs.sort_by(|a, b| a.path.cmp(&b.path));
+        if refreshed > 0 {
+            self.changed = true;
+        }
+        refreshed
+    }
+
+    /// Returns the indexed files of the directories, keeping only the file with the highest
+    /// precedence for every ID. The directories have to be refreshed first.
+    pub fn files(&self, dirs: &[PathBuf]) -> Vec<&IndexedFile> {
+        let mut files: HashMap<&str, &IndexedFile> = HashMap::new();
+        for dir in dirs {
+            if let Some(indexed_dir) = self
+                .dirs
+                .iter()
+                .find(|indexed_dir| &indexed_dir.path == dir)
+            {
+                for file in &indexed_dir.files {
+                    files.entry(&file.id).or_insert(file);
+                }
+            }
+        }
+        files.into_values().collect()
+    }
+
+    /// Returns the entries of the directories passing the checker, with the parse errors as
+    /// messages. The contents of the matching files are read only if requested, they are empty
+    /// otherwise. The directories have to be refreshed first.
+    pub fn load_entries(
+        &self,
+        dirs: &[PathBuf],
+        checker: &Checker,
+        with_contents: bool,
+    ) -> (Vec<MatchedEntry>, Vec<String>) {
+        let mut matches = Vec::new();
+        let mut errors = Vec::new();
+
+        for file in self.files(dirs) {
+            match &file.entry {
+                Ok(entry) => {
+                    if !checker.check_entry(entry) {
+                        continue;
+                    }
+                    let contents = if with_contents {
+                        match fs::read_to_string(&file.path) {
+                            Ok(contents) => contents,
+                            Err(error) => {
+                                errors.push(format!(
+                                    "Could not read file {}: {}",
+                                    file.path.display(),
+                                    error
+                                ));
+                                continue;
+                            }
+                        }
+                    } else {
+                        String::new()
+                    };
+                    matches.push(MatchedEntry {
+                        id: file.id.clone(),
+                        path: file.path.clone(),
+                        contents,
+                        entry: entry.clone(),
+                        score: None,
+                    });
+                }
+                Err(error) => errors.push(format!("{} in {}", error, file.path.display())),
+            }
+        }
+        (matches, errors)
+    }
+
+    /// Stores the index if it changed since it was loaded.
+    pub fn save(&mut self) -> io::Result<()> {
+        if !self.changed {
+            return Ok(());
+        }
+        let path = match &self.path {
+            Some(path) => path,
+            None => {
+                return Err(io::Error::new(
+                    io::ErrorKind::NotFound,
+                    "could not determine the cache directory",
+                ))
+            }
+        };
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        let contents = serde_json::to_vec(self)?;
+        let temp_path = path.with_extension(format!("{}.tmp", process::id()));
+        fs::write(&temp_path, contents)?;
+        fs::rename(&temp_path, path)?;
+        self.changed = false;
+        Ok(())
+    }
+}
+
+impl Default for Index {
+    fn default() -> Self {
+        Index::new()
+    }
+}
+
+/// Refreshes a single directory, returning the number of files read and parsed again and whether
+/// the directory had to be scanned again.
+fn refresh_dir(dir: &Path, cached: Option<IndexedDir>, jobs: usize) -> (IndexedDir, usize, bool) {
+    let dirs_unchanged = cached.as_ref().is_some_and(|cached| {
+        cached
+            .dir_mtimes
+            .iter()
+            .all(|(path, mtime)| Mtime::of(path).map(|(mtime, _)| mtime) == *mtime)
+    });
+
+    let mut cached_files: HashMap<PathBuf, IndexedFile> = HashMap::new();
+    let mut dir_mtimes = Vec::new();
+    let sources: Vec<(String, PathBuf)> = match cached {
+        Some(cached) if dirs_unchanged => {
+            dir_mtimes = cached.dir_mtimes;
+            cached
+                .files
+                .into_iter()
+                .map(|file| {
+                    let source = (file.id.clone(), file.path.clone());
+                    cached_files.insert(file.path.clone(), file);
+                    source
+                })
+                .collect()
+        }
+        cached => {
+            if let Some(cached) = cached {
+                for file in cached.files {
+                    cached_files.insert(file.path.clone(), file);
+                }
+            }
+
+            let mut entries = HashMap::new();
+            let mut scanned_dirs = Vec::new();
+            discovery::scan_path(
+                &discovery::base_path(dir),
+                dir,
+                EntryKind::Desktop,
+                &mut entries,
+                &mut scanned_dirs,
+            );
+            for scanned_dir in scanned_dirs {
+                let mtime = Mtime::of(&scanned_dir).map(|(mtime, _)| mtime);
+                dir_mtimes.push((scanned_dir, mtime));
+            }
+
+            let mut sources: Vec<(String, PathBuf)> = entries.into_iter().collect();
+            sources.sort();
+            sources
+        }
+    };
+
+    let results = parallel::map(&sources, jobs, |(id, path)| {
+        let (mtime, size) = Mtime::of(path)?;
+        if let Some(file) = cached_files.get(path) {
+            if file.id == *id && file.mtime == mtime && file.size == size {
+                return Some((file.clone(), false));
+            }
+        }
+
+        let contents = fs::read_to_string(path).ok()?;
+        let entry = Entry::new(&contents).map_err(|error| error.to_string());
+        let file = IndexedFile {
+            id: id.clone(),
+            path: path.clone(),
+            mtime,
+            size,
+            entry,
+        };
+        Some((file, true))
+    });
+
+    let mut files = Vec::with_capacity(results.len());
+    let mut refreshed = 0;
+    for (result, (_, path)) in results.into_iter().zip(&sources) {
+        match result {
+            Some((file, changed)) => {
+                if changed {
+                    refreshed += 1;
+                }
+                files.push(file);
+            }
+            None => eprintln!("Could not read file {}", path.display()),
+        }
+    }
+
+    let indexed_dir = IndexedDir {
+        path: dir.to_path_buf(),
+        dir_mtimes,
+        files,
+    };
+    (indexed_dir, refreshed, !dirs_unchanged)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::configuration::Configuration;
+    use crate::tempdir::TempDir;
+    use std::thread;
+    use std::time::Duration;
+    use structopt::StructOpt;
+
+    fn ids(index: &Index, dirs: &[PathBuf]) -> Vec<String> {
+        let mut ids: Vec<String> = index
+            .files(dirs)
+            .iter()
+            .map(|file| file.id.clone())
+            .collect();
+        ids.sort();
+        ids
+    }
+
+    #[test]
+    fn test_refresh() -> io::Result<()> {
+        let root = TempDir::new("cache");
+        let applications = root.join("applications");
+        fs::create_dir_all(applications.join("vendor"))?;
+        fs::write(
+            applications.join("foo.desktop"),
+            "[Desktop Entry]\nName=Foo\n",
+        )?;
+        fs::write(applications.join("vendor/bar.desktop"), "Name=Bar\n")?;
+        let dirs = vec![applications.clone()];
+        let index_path = root.join("index.json");
+
+        let mut index = Index::load(&index_path);
+        assert_eq!(index.refresh(&dirs, 2), 2);
+        assert_eq!(
+            ids(&index, &dirs),
+            vec!["foo.desktop", "vendor-bar.desktop"]
+        );
+        index.save()?;
+
+        let mut index = Index::load(&index_path);
+        assert_eq!(index.refresh(&dirs, 2), 0);
+
+        thread::sleep(Duration::from_millis(10));
+        fs::write(
+            applications.join("foo.desktop"),
+            "[Desktop Entry]\nName=Foo 2\n",
+        )?;
+        fs::remove_file(applications.join("vendor/bar.desktop"))?;
+        fs::write(applications.join("vendor/baz.desktop"), "invalid\n")?;
+        assert_eq!(index.refresh(&dirs, 2), 2);
+        assert_eq!(
+            ids(&index, &dirs),
+            vec!["foo.desktop", "vendor-baz.desktop"]
+        );
+
+        let files = index.files(&dirs);
+        let foo = files.iter().find(|file| file.id == "foo.desktop").unwrap();
+        assert_eq!(
+            foo.entry.as_ref().unwrap().get("Name"),
+            Some(&String::from("Foo 2"))
+        );
+        let baz = files
+            .iter()
+            .find(|file| file.id == "vendor-baz.desktop")
+            .unwrap();
+        assert!(baz.entry.is_err());
+
+        let checker = Checker::new(Configuration::from_iter(&["desktopentries"]));
+        let (matches, errors) = index.load_entries(&dirs, &checker, false);
+        assert_eq!(matches.len(), 1);
+        assert!(matches[0].contents.is_empty());
+        assert_eq!(errors.len(), 1);
+        let (matches, _) = index.load_entries(&dirs, &checker, true);
+        assert_eq!(matches[0].contents, "[Desktop Entry]\nName=Foo 2\n");
+
+        let other = root.join("other");
+        fs::create_dir_all(&other)?;
+        fs::write(other.join("qux.desktop"), "[Desktop Entry]\nName=Qux\n")?;
+        let other_dirs = vec![other];
+        assert_eq!(index.refresh(&other_dirs, 2), 1);
+        assert_eq!(ids(&index, &other_dirs), vec!["qux.desktop"]);
+        assert!(ids(&index, &dirs).is_empty());
+        assert_eq!(index.dirs.len(), 1);
+
+        Ok(())
+    }
+}
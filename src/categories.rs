@@ -0,0 +1,428 @@
+use std::collections::BTreeMap;
+
+use super::entry::Entry;
+use super::menu::{self, Menu, MenuItem};
+use super::output::MatchedEntry;
+
+/// The registered main categories of the Desktop Menu Specification.
+pub static MAIN_CATEGORIES: &[&str] = &[
+    "AudioVideo",
+    "Audio",
+    "Video",
+    "Development",
+    "Education",
+    "Game",
+    "Graphics",
+    "Network",
+    "Office",
+    "Science",
+    "Settings",
+    "System",
+    "Utility",
+];
+
+/// The registered main categories which should be used together with another main category,
+/// with the categories they require in the same form as the additional categories.
+pub static DEPENDENT_MAIN_CATEGORIES: &[(&str, &[&[&str]])] =
+    &[("Audio", &[&["AudioVideo"]]), ("Video", &[&["AudioVideo"]])];
+
+/// The registered additional categories with their related categories. An entry using an
+/// additional category should also use all categories of one of the alternatives.
+pub static ADDITIONAL_CATEGORIES: &[(&str, &[&[&str]])] = &[
+    ("Building", &[&["Development"]]),
+    ("Debugger", &[&["Development"]]),
+    ("IDE", &[&["Development"]]),
+    ("GUIDesigner", &[&["Development"]]),
+    ("Profiling", &[&["Development"]]),
+    ("RevisionControl", &[&["Development"]]),
+    ("Translation", &[&["Development"]]),
+    ("Calendar", &[&["Office"]]),
+    ("ContactManagement", &[&["Office"]]),
+    (
+        "Database",
+        &[&["Office"], &["Development"], &["AudioVideo"]],
+    ),
+    ("Dictionary", &[&["Office", "TextTools"]]),
+    ("Chart", &[&["Office"]]),
+    ("Email", &[&["Office", "Network"]]),
+    ("Finance", &[&["Office"]]),
+    ("FlowChart", &[&["Office"]]),
+    ("PDA", &[&["Office"]]),
+    ("ProjectManagement", &[&["Office", "Development"]]),
+    ("Presentation", &[&["Office"]]),
+    ("Spreadsheet", &[&["Office"]]),
+    ("WordProcessor", &[&["Office"]]),
+    ("2DGraphics", &[&["Graphics"]]),
+    ("VectorGraphics", &[&["Graphics", "2DGraphics"]]),
+    ("RasterGraphics", &[&["Graphics", "2DGraphics"]]),
+    ("3DGraphics", &[&["Graphics"]]),
+    ("Scanning", &[&["Graphics"]]),
+    ("OCR", &[&["Graphics", "Scanning"]]),
+    ("Photography", &[&["Graphics"], &["Office"]]),
+    ("Publishing", &[&["Graphics"], &["Office"]]),
+    ("Viewer", &[&["Graphics"], &["Office"]]),
+    ("TextTools", &[&["Utility"]]),
+    ("DesktopSettings", &[&["Settings"]]),
+    ("HardwareSettings", &[&["Settings"]]),
+    ("Printing", &[&["HardwareSettings", "Settings"]]),
+    ("PackageManager", &[&["Settings"]]),
+    ("Dialup", &[&["Network"]]),
+    ("InstantMessaging", &[&["Network"]]),
+    ("Chat", &[&["Network"]]),
+    ("IRCClient", &[&["Network"]]),
+    ("Feed", &[&["Network"]]),
+    ("FileTransfer", &[&["Network"]]),
+    ("HamRadio", &[&["Network"], &["Audio"]]),
+    ("News", &[&["Network"]]),
+    ("P2P", &[&["Network"]]),
+    ("RemoteAccess", &[&["Network"]]),
+    ("Telephony", &[&["Network"]]),
+    ("TelephonyTools", &[&["Utility"]]),
+    ("VideoConference", &[&["Network"]]),
+    ("WebBrowser", &[&["Network"]]),
+    ("WebDevelopment", &[&["Network"], &["Development"]]),
+    ("Midi", &[&["AudioVideo", "Audio"]]),
+    ("Mixer", &[&["AudioVideo", "Audio"]]),
+    ("Sequencer", &[&["AudioVideo", "Audio"]]),
+    ("Tuner", &[&["AudioVideo", "Audio"]]),
+    ("TV", &[&["AudioVideo", "Video"]]),
+    (
+        "AudioVideoEditing",
+        &[&["Audio"], &["Video"], &["AudioVideo"]],
+    ),
+    ("Player", &[&["Audio"], &["Video"], &["AudioVideo"]]),
+    ("Recorder", &[&["Audio"], &["Video"], &["AudioVideo"]]),
+    ("DiscBurning", &[&["AudioVideo"]]),
+    ("ActionGame", &[&["Game"]]),
+    ("AdventureGame", &[&["Game"]]),
+    ("ArcadeGame", &[&["Game"]]),
+    ("BoardGame", &[&["Game"]]),
+    ("BlocksGame", &[&["Game"]]),
+    ("CardGame", &[&["Game"]]),
+    ("KidsGame", &[&["Game"]]),
+    ("LogicGame", &[&["Game"]]),
+    ("RolePlaying", &[&["Game"]]),
+    ("Shooter", &[&["Game"]]),
+    ("Simulation", &[&["Game"]]),
+    ("SportsGame", &[&["Game"]]),
+    ("StrategyGame", &[&["Game"]]),
+    ("Art", &[&["Education"], &["Science"]]),
+    ("Construction", &[&["Education"], &["Science"]]),
+    ("Music", &[&["AudioVideo"], &["Education"]]),
+    ("Languages", &[&["Education"], &["Science"]]),
+    ("ArtificialIntelligence", &[&["Education"], &["Science"]]),
+    ("Astronomy", &[&["Education"], &["Science"]]),
+    ("Biology", &[&["Education"], &["Science"]]),
+    ("Chemistry", &[&["Education"], &["Science"]]),
+    ("ComputerScience", &[&["Education"], &["Science"]]),
+    ("DataVisualization", &[&["Education"], &["Science"]]),
+    ("Economy", &[&["Education"], &["Science"]]),
+    ("Electricity", &[&["Education"], &["Science"]]),
+    ("Geography", &[&["Education"], &["Science"]]),
+    ("Geology", &[&["Education"], &["Science"]]),
+    ("Geoscience", &[&["Education"], &["Science"]]),
+    ("History", &[&["Education"], &["Science"]]),
+    ("Humanities", &[&["Education"], &["Science"]]),
+    ("ImageProcessing", &[&["Education"], &["Science"]]),
+    ("Literature", &[&["Education"], &["Science"]]),
+    ("Maps", &[&["Education"], &["Science"], &["Utility"]]),
+    ("Math", &[&["Education"], &["Science"]]),
+    (
+        "NumericalAnalysis",
+        &[&["Education", "Math"], &["Science", "Math"]],
+    ),
+    ("MedicalSoftware", &[&["Education"], &["Science"]]),
+    ("Physics", &[&["Education"], &["Science"]]),
+    ("Robotics", &[&["Education"], &["Science"]]),
+    (
+        "Spirituality",
+        &[&["Education"], &["Science"], &["Utility"]],
+    ),
+    ("Sports", &[&["Education"], &["Science"]]),
+    (
+        "ParallelComputing",
+        &[
+            &["Education", "ComputerScience"],
+            &["Science", "ComputerScience"],
+        ],
+    ),
+    ("Amusement", &[]),
+    ("Archiving", &[&["Utility"]]),
+    ("Compression", &[&["Utility"]]),
+    ("Electronics", &[]),
+    ("Emulator", &[&["System"], &["Game"]]),
+    ("Engineering", &[]),
+    ("FileTools", &[&["Utility"], &["System"]]),
+    ("FileManager", &[&["System", "FileTools"]]),
+    ("TerminalEmulator", &[&["System"]]),
+    ("Filesystem", &[&["System"]]),
+    ("Monitor", &[&["System"]]),
+    ("Security", &[&["Settings"], &["System"]]),
+    ("Accessibility", &[&["Settings"], &["Utility"]]),
+    ("Calculator", &[&["Utility"]]),
+    ("Clock", &[&["Utility"]]),
+    ("TextEditor", &[&["Utility"]]),
+    ("Documentation", &[]),
+    ("Adult", &[]),
+    ("Core", &[]),
+    ("KDE", &[&["Qt"]]),
+    ("GNOME", &[&["GTK"]]),
+    ("XFCE", &[&["GTK"]]),
+    ("DDE", &[&["Qt"]]),
+    ("GTK", &[]),
+    ("Qt", &[]),
+    ("Motif", &[]),
+    ("Java", &[]),
+    ("ConsoleOnly", &[]),
+];
+
+/// The submenus of the category menu with their labels, in the order they are shown. Audio and
+/// Video entries are shown under AudioVideo.
+static MENU_CATEGORIES: &[(&str, &str)] = &[
+    ("AudioVideo", "Multimedia"),
+    ("Development", "Development"),
+    ("Education", "Education"),
+    ("Game", "Games"),
+    ("Graphics", "Graphics"),
+    ("Network", "Internet"),
+    ("Office", "Office"),
+    ("Science", "Science"),
+    ("Settings", "Settings"),
+    ("System", "System"),
+    ("Utility", "Accessories"),
+    (OTHER, "Other"),
+];
+
+/// The submenu of the entries without a main category.
+pub static OTHER: &str = "Other";
+
+/// The keys needed to build the category menu.
+pub static KEYS: &[&str] = &[
+    "Type",
+    "Name",
+    "Icon",
+    "Exec",
+    "Terminal",
+    "Categories",
+    "NoDisplay",
+    "Hidden",
+    "OnlyShowIn",
+    "NotShowIn",
+    "TryExec",
+];
+
+/// Returns the related categories of the additional category or of the dependent main category,
+/// or none if the category does not require any.
+pub fn related_categories(category: &str) -> Option<&'static [&'static [&'static str]]> {
+    ADDITIONAL_CATEGORIES
+        .iter()
+        .chain(DEPENDENT_MAIN_CATEGORIES)
+        .find(|(name, _)| *name == category)
+        .map(|(_, related)| *related)
+}
+
+/// Returns the main category the entry is listed under, the first main category of the
+/// Categories value or the main category related to its first additional category which has one.
+/// Dependent main categories such as Audio are listed under the main category they require.
+pub fn main_category(categories: &[&str]) -> Option<&'static str> {
+    let main = categories
+        .iter()
+        .find_map(|category| find_main(category))
+        .or_else(|| {
+            categories
+                .iter()
+                .find_map(|category| related_main(category, 0))
+        })?;
+    let required = DEPENDENT_MAIN_CATEGORIES
+        .iter()
+        .find(|(name, _)| *name == main)
+        .and_then(|(_, related)| related.first()?.first());
+    Some(required.copied().unwrap_or(main))
+}
+
+fn find_main(category: &str) -> Option<&'static str> {
+    MAIN_CATEGORIES
+        .iter()
+        .find(|main| **main == category)
+        .copied()
+}
+
+/// Follows the first alternative of related categories, which may relate to other additional
+/// categories, until a main category is found.
+fn related_main(category: &str, depth: usize) -> Option<&'static str> {
+    let alternative = related_categories(category)?.first()?;
+    alternative
+        .iter()
+        .find_map(|related| find_main(related))
+        .or_else(|| {
+            if depth > 2 {
+                return None;
+            }
+            alternative
+                .iter()
+                .find_map(|related| related_main(related, depth + 1))
+        })
+}
+
+/// Groups the entries shown in the desktop environments into submenus by their main
+/// categories, entries without one are grouped under Other. The submenus are ordered as the main
+/// categories are listed by the specification, the entries by their labels.
+pub fn category_menu(
+    matches: &[MatchedEntry],
+    desktops: &[String],
+    language_strings: &[String],
+) -> Menu {
+    let mut groups: BTreeMap<&str, Vec<MenuItem>> = BTreeMap::new();
+    for matched in matches {
+        if !menu::is_shown(&matched.entry, desktops) {
+            continue;
+        }
+        let main = main_category(&categories(&matched.entry)).unwrap_or(OTHER);
+        let entry = menu::menu_entry(&matched.id, &matched.path, &matched.entry, language_strings);
+        groups.entry(main).or_default().push(MenuItem::Entry(entry));
+    }
+
+    let items = MENU_CATEGORIES
+        .iter()
+        .filter_map(|(name, label)| {
+            let mut items = groups.remove(name)?;
+            items.sort_by_key(|item| item.label().to_lowercase());
+            Some(MenuItem::Menu(Menu {
+                name: String::from(*name),
+                label: String::from(*label),
+                icon: None,
+                directory: None,
+                items,
+            }))
+        })
+        .collect();
+    Menu {
+        name: String::from("Applications"),
+        label: String::from("Applications"),
+        icon: None,
+        directory: None,
+        items,
+    }
+}
+
+/// Returns the values of the entry's Categories key.
+pub fn categories(entry: &Entry) -> Vec<&str> {
+    entry
+        .get("Categories")
+        .map(|value| parse(value))
+        .unwrap_or_default()
+}
+
+/// Splits a Categories value into the categories.
+pub fn parse(value: &str) -> Vec<&str> {
+    value
+        .split(';')
+        .map(str::trim)
+        .filter(|category| !category.is_empty())
+        .collect()
+}
+
+/// Returns whether the category is a registered main or additional category.
+pub fn is_registered(category: &str) -> bool {
+    find_main(category).is_some() || related_categories(category).is_some()
+}
+
+/// Returns the registered category the unknown category is most likely a misspelling of, one
+/// differing only in case or by at most two edits.
+pub fn suggestion(category: &str) -> Option<&'static str> {
+    let lowercase = category.to_lowercase();
+    MAIN_CATEGORIES
+        .iter()
+        .chain(ADDITIONAL_CATEGORIES.iter().map(|(name, _)| name))
+        .map(|name| (edit_distance(&lowercase, &name.to_lowercase()), *name))
+        .filter(|(distance, _)| *distance <= 2)
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, name)| name)
+}
+
+/// Returns the Levenshtein distance of the strings.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, a_char) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, b_char) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(a_char != *b_char);
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn matched(id: &str, keys: &str) -> MatchedEntry {
+        let contents = format!(
+            "[Desktop Entry]\nType=Application\nName={}\nExec={} %U\n{}",
+            id.to_uppercase(),
+            id,
+            keys
+        );
+        MatchedEntry {
+            id: format!("{}.desktop", id),
+            path: PathBuf::from(format!("/usr/share/applications/{}.desktop", id)),
+            entry: Entry::new(&contents).unwrap(),
+            contents,
+            score: None,
+        }
+    }
+
+    #[test]
+    fn test_main_category() {
+        assert_eq!(main_category(&["GTK", "Utility", "Game"]), Some("Utility"));
+        assert_eq!(main_category(&["Audio", "Player"]), Some("AudioVideo"));
+        assert_eq!(main_category(&["Qt", "WebBrowser"]), Some("Network"));
+        assert_eq!(main_category(&["FileManager"]), Some("System"));
+        assert_eq!(main_category(&["Printing"]), Some("Settings"));
+        assert_eq!(main_category(&["Qt", "KDE"]), None);
+        assert_eq!(main_category(&["Unknown"]), None);
+    }
+
+    #[test]
+    fn test_suggestion() {
+        assert!(is_registered("TextEditor"));
+        assert!(!is_registered("texteditor"));
+        assert_eq!(suggestion("texteditor"), Some("TextEditor"));
+        assert_eq!(suggestion("Utilty"), Some("Utility"));
+        assert_eq!(suggestion("Games"), Some("Game"));
+        assert_eq!(suggestion("Something"), None);
+    }
+
+    #[test]
+    fn test_category_menu() {
+        let matches = vec![
+            matched("vim", "Categories=Utility;TextEditor;\n"),
+            matched("calc", "Categories=Calculator;\n"),
+            matched("firefox", "Categories=Network;WebBrowser;\n"),
+            matched("hidden", "Categories=Network;\nNoDisplay=true\n"),
+            matched("tool", ""),
+        ];
+
+        let menu = category_menu(&matches, &[], &[]);
+        let names: Vec<&str> = menu
+            .items
+            .iter()
+            .map(|item| match item {
+                MenuItem::Menu(menu) => menu.label.as_str(),
+                _ => "",
+            })
+            .collect();
+        assert_eq!(names, vec!["Internet", "Accessories", "Other"]);
+        match &menu.items[1] {
+            MenuItem::Menu(accessories) => {
+                let labels: Vec<&str> = accessories.items.iter().map(MenuItem::label).collect();
+                assert_eq!(labels, vec!["CALC", "VIM"]);
+            }
+            _ => panic!("Expected a submenu"),
+        }
+    }
+}
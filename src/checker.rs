@@ -2,14 +2,16 @@ use regex::Regex;
 use std::collections::HashMap;
 use std::env;
 
+use super::categories;
 use super::configuration::Configuration;
-use super::entry::Entry;
+use super::entry::{Entry, ParseEntryError};
 
 static TRUE: &str = "true";
 static FALSE: &str = "false";
 
 pub struct Checker {
     checks: Vec<Box<dyn Check>>,
+    language_strings: Vec<String>,
 }
 
 impl Checker {
@@ -18,7 +20,7 @@ impl Checker {
         let mut language_strings = Vec::with_capacity(0);
 
         if conf.localized {
-            if let Some(lang) = env::var("LC_MESSAGES").ok() {
+            if let Ok(lang) = env::var("LC_MESSAGES") {
                 language_strings = prepare_language_strings(lang);
             }
         }
@@ -126,6 +128,10 @@ impl Checker {
             checks.push(Box::new(CategoriesCheck { regex_list }))
         }
 
+        if let Some(regex) = conf.main_category {
+            checks.push(Box::new(MainCategoryCheck { regex }))
+        }
+
         if let Some(regex_list) = conf.implements {
             checks.push(Box::new(ImplementsCheck { regex_list }))
         }
@@ -156,7 +162,14 @@ impl Checker {
             checks.push(Box::new(NotPrefersNonDefaultGPUCheck {}));
         }
 
-        Checker { checks }
+        Checker {
+            checks,
+            language_strings,
+        }
+    }
+
+    pub fn language_strings(&self) -> &[String] {
+        &self.language_strings
     }
 
     pub fn check_entry(&self, entry: &Entry) -> bool {
@@ -167,10 +180,53 @@ impl Checker {
         }
         true
     }
+
+    /// Parses the entry, storing only the keys read by the checks and the extra keys, localized
+    /// variants included. All keys are stored if no extra keys are specified. The parsing stops as
+    /// soon as all keys of a check were read and the check failed. Returns None if the entry did
+    /// not pass the checks, in which case the rest of the entry is not validated.
+    pub fn parse_entry(
+        &self,
+        contents: &str,
+        extra_keys: Option<&[&str]>,
+    ) -> Result<Option<Entry>, ParseEntryError> {
+        let check_keys: Vec<Vec<&str>> = self.checks.iter().map(|check| check.keys()).collect();
+        let mut seen_keys: Vec<Vec<bool>> = check_keys
+            .iter()
+            .map(|keys| vec![false; keys.len()])
+            .collect();
+
+        let filter = |key: &str| match extra_keys {
+            Some(extra_keys) => {
+                let base_key = key.split('[').next().unwrap_or(key);
+                extra_keys.contains(&base_key) || check_keys.iter().any(|keys| keys.contains(&key))
+            }
+            None => true,
+        };
+        let callback = |key: &str, entries: &HashMap<String, String>| {
+            for (index, keys) in check_keys.iter().enumerate() {
+                if let Some(position) = keys.iter().position(|check_key| *check_key == key) {
+                    seen_keys[index][position] = true;
+                    if seen_keys[index].iter().all(|seen| *seen)
+                        && !self.checks[index].check(entries)
+                    {
+                        return false;
+                    }
+                }
+            }
+            true
+        };
+
+        let entry = Entry::parse_filtered(contents, filter, callback)?;
+        Ok(entry.filter(|entry| self.check_entry(entry)))
+    }
 }
 
-trait Check {
+trait Check: Send + Sync {
     fn check(&self, entries: &HashMap<String, String>) -> bool;
+
+    /// Returns the keys read by the check.
+    fn keys(&self) -> Vec<&str>;
 }
 
 struct ApplicationCheck {}
@@ -182,6 +238,10 @@ impl Check for ApplicationCheck {
             None => false,
         }
     }
+
+    fn keys(&self) -> Vec<&str> {
+        vec!["Type"]
+    }
 }
 struct LinkCheck {}
 impl Check for LinkCheck {
@@ -192,6 +252,10 @@ impl Check for LinkCheck {
             None => false,
         }
     }
+
+    fn keys(&self) -> Vec<&str> {
+        vec!["Type"]
+    }
 }
 struct DirectoryCheck {}
 impl Check for DirectoryCheck {
@@ -202,6 +266,10 @@ impl Check for DirectoryCheck {
             None => false,
         }
     }
+
+    fn keys(&self) -> Vec<&str> {
+        vec!["Type"]
+    }
 }
 
 struct NotApplicationCheck {}
@@ -213,6 +281,10 @@ impl Check for NotApplicationCheck {
             None => false,
         }
     }
+
+    fn keys(&self) -> Vec<&str> {
+        vec!["Type"]
+    }
 }
 struct NotLinkCheck {}
 impl Check for NotLinkCheck {
@@ -223,6 +295,10 @@ impl Check for NotLinkCheck {
             None => false,
         }
     }
+
+    fn keys(&self) -> Vec<&str> {
+        vec!["Type"]
+    }
 }
 struct NotDirectoryCheck {}
 impl Check for NotDirectoryCheck {
@@ -233,6 +309,10 @@ impl Check for NotDirectoryCheck {
             None => false,
         }
     }
+
+    fn keys(&self) -> Vec<&str> {
+        vec!["Type"]
+    }
 }
 
 struct VersionCheck {
@@ -242,6 +322,10 @@ impl Check for VersionCheck {
     fn check(&self, entries: &HashMap<String, String>) -> bool {
         check_string_entry("Version", &self.regex, entries)
     }
+
+    fn keys(&self) -> Vec<&str> {
+        vec!["Version"]
+    }
 }
 
 struct NameCheck {
@@ -261,6 +345,10 @@ impl Check for NameCheck {
     fn check(&self, entries: &HashMap<String, String>) -> bool {
         check_localized_entry(&self.regex, &self.localized_keys, entries)
     }
+
+    fn keys(&self) -> Vec<&str> {
+        self.localized_keys.iter().map(String::as_str).collect()
+    }
 }
 
 struct GenericNameCheck {
@@ -280,6 +368,10 @@ impl Check for GenericNameCheck {
     fn check(&self, entries: &HashMap<String, String>) -> bool {
         check_localized_entry(&self.regex, &self.localized_keys, entries)
     }
+
+    fn keys(&self) -> Vec<&str> {
+        self.localized_keys.iter().map(String::as_str).collect()
+    }
 }
 
 struct NoDisplayCheck {}
@@ -287,12 +379,20 @@ impl Check for NoDisplayCheck {
     fn check(&self, entries: &HashMap<String, String>) -> bool {
         check_entry_true("NoDisplay", entries)
     }
+
+    fn keys(&self) -> Vec<&str> {
+        vec!["NoDisplay"]
+    }
 }
 struct NotNoDisplayCheck {}
 impl Check for NotNoDisplayCheck {
     fn check(&self, entries: &HashMap<String, String>) -> bool {
         check_entry_false("NoDisplay", entries)
     }
+
+    fn keys(&self) -> Vec<&str> {
+        vec!["NoDisplay"]
+    }
 }
 
 struct CommentCheck {
@@ -312,6 +412,10 @@ impl Check for CommentCheck {
     fn check(&self, entries: &HashMap<String, String>) -> bool {
         check_localized_entry(&self.regex, &self.localized_keys, entries)
     }
+
+    fn keys(&self) -> Vec<&str> {
+        self.localized_keys.iter().map(String::as_str).collect()
+    }
 }
 
 struct IconCheck {
@@ -321,6 +425,10 @@ impl Check for IconCheck {
     fn check(&self, entries: &HashMap<String, String>) -> bool {
         check_string_entry("Icon", &self.regex, entries)
     }
+
+    fn keys(&self) -> Vec<&str> {
+        vec!["Icon"]
+    }
 }
 
 struct HiddenCheck {}
@@ -328,12 +436,20 @@ impl Check for HiddenCheck {
     fn check(&self, entries: &HashMap<String, String>) -> bool {
         check_entry_true("Hidden", entries)
     }
+
+    fn keys(&self) -> Vec<&str> {
+        vec!["Hidden"]
+    }
 }
 struct NotHiddenCheck {}
 impl Check for NotHiddenCheck {
     fn check(&self, entries: &HashMap<String, String>) -> bool {
         check_entry_false("Hidden", entries)
     }
+
+    fn keys(&self) -> Vec<&str> {
+        vec!["Hidden"]
+    }
 }
 
 struct OnlyShowInCheck {
@@ -343,6 +459,10 @@ impl Check for OnlyShowInCheck {
     fn check(&self, entries: &HashMap<String, String>) -> bool {
         check_multi_string_entry("OnlyShowIn", &self.regex_list, entries)
     }
+
+    fn keys(&self) -> Vec<&str> {
+        vec!["OnlyShowIn"]
+    }
 }
 struct NotShowInCheck {
     regex_list: Vec<Regex>,
@@ -351,6 +471,10 @@ impl Check for NotShowInCheck {
     fn check(&self, entries: &HashMap<String, String>) -> bool {
         check_multi_string_entry("NotShowIn", &self.regex_list, entries)
     }
+
+    fn keys(&self) -> Vec<&str> {
+        vec!["NotShowIn"]
+    }
 }
 
 struct DBusActivatableCheck {}
@@ -358,12 +482,20 @@ impl Check for DBusActivatableCheck {
     fn check(&self, entries: &HashMap<String, String>) -> bool {
         check_entry_true("DBusActivatable", entries)
     }
+
+    fn keys(&self) -> Vec<&str> {
+        vec!["DBusActivatable"]
+    }
 }
 struct NotDBusActivatableCheck {}
 impl Check for NotDBusActivatableCheck {
     fn check(&self, entries: &HashMap<String, String>) -> bool {
         check_entry_false("DBusActivatable", entries)
     }
+
+    fn keys(&self) -> Vec<&str> {
+        vec!["DBusActivatable"]
+    }
 }
 
 struct TryExecCheck {
@@ -373,6 +505,10 @@ impl Check for TryExecCheck {
     fn check(&self, entries: &HashMap<String, String>) -> bool {
         check_string_entry("TryExec", &self.regex, entries)
     }
+
+    fn keys(&self) -> Vec<&str> {
+        vec!["TryExec"]
+    }
 }
 
 struct ExecCheck {
@@ -382,6 +518,10 @@ impl Check for ExecCheck {
     fn check(&self, entries: &HashMap<String, String>) -> bool {
         check_string_entry("Exec", &self.regex, entries)
     }
+
+    fn keys(&self) -> Vec<&str> {
+        vec!["Exec"]
+    }
 }
 
 struct PathCheck {
@@ -391,6 +531,10 @@ impl Check for PathCheck {
     fn check(&self, entries: &HashMap<String, String>) -> bool {
         check_string_entry("Path", &self.regex, entries)
     }
+
+    fn keys(&self) -> Vec<&str> {
+        vec!["Path"]
+    }
 }
 
 struct TerminalCheck {}
@@ -398,12 +542,20 @@ impl Check for TerminalCheck {
     fn check(&self, entries: &HashMap<String, String>) -> bool {
         check_entry_true("Terminal", entries)
     }
+
+    fn keys(&self) -> Vec<&str> {
+        vec!["Terminal"]
+    }
 }
 struct NotTerminalCheck {}
 impl Check for NotTerminalCheck {
     fn check(&self, entries: &HashMap<String, String>) -> bool {
         check_entry_false("Terminal", entries)
     }
+
+    fn keys(&self) -> Vec<&str> {
+        vec!["Terminal"]
+    }
 }
 
 struct ActionsCheck {
@@ -413,6 +565,10 @@ impl Check for ActionsCheck {
     fn check(&self, entries: &HashMap<String, String>) -> bool {
         check_multi_string_entry("Actions", &self.regex_list, entries)
     }
+
+    fn keys(&self) -> Vec<&str> {
+        vec!["Actions"]
+    }
 }
 
 struct MimeTypeCheck {
@@ -422,6 +578,10 @@ impl Check for MimeTypeCheck {
     fn check(&self, entries: &HashMap<String, String>) -> bool {
         check_multi_string_entry("MimeType", &self.regex_list, entries)
     }
+
+    fn keys(&self) -> Vec<&str> {
+        vec!["MimeType"]
+    }
 }
 
 struct CategoriesCheck {
@@ -431,6 +591,27 @@ impl Check for CategoriesCheck {
     fn check(&self, entries: &HashMap<String, String>) -> bool {
         check_multi_string_entry("Categories", &self.regex_list, entries)
     }
+
+    fn keys(&self) -> Vec<&str> {
+        vec!["Categories"]
+    }
+}
+
+struct MainCategoryCheck {
+    regex: Regex,
+}
+impl Check for MainCategoryCheck {
+    fn check(&self, entries: &HashMap<String, String>) -> bool {
+        let main_category = entries
+            .get("Categories")
+            .and_then(|value| categories::main_category(&categories::parse(value)))
+            .unwrap_or(categories::OTHER);
+        self.regex.is_match(main_category)
+    }
+
+    fn keys(&self) -> Vec<&str> {
+        vec!["Categories"]
+    }
 }
 
 struct ImplementsCheck {
@@ -440,6 +621,10 @@ impl Check for ImplementsCheck {
     fn check(&self, entries: &HashMap<String, String>) -> bool {
         check_multi_string_entry("Implements", &self.regex_list, entries)
     }
+
+    fn keys(&self) -> Vec<&str> {
+        vec!["Implements"]
+    }
 }
 
 struct KeywordsCheck {
@@ -464,6 +649,10 @@ impl Check for KeywordsCheck {
         }
         false
     }
+
+    fn keys(&self) -> Vec<&str> {
+        self.localized_keys.iter().map(String::as_str).collect()
+    }
 }
 
 struct StartupNotifyCheck {}
@@ -471,12 +660,20 @@ impl Check for StartupNotifyCheck {
     fn check(&self, entries: &HashMap<String, String>) -> bool {
         check_entry_true("StartupNotify", entries)
     }
+
+    fn keys(&self) -> Vec<&str> {
+        vec!["StartupNotify"]
+    }
 }
 struct NotStartupNotifyCheck {}
 impl Check for NotStartupNotifyCheck {
     fn check(&self, entries: &HashMap<String, String>) -> bool {
         check_entry_false("StartupNotify", entries)
     }
+
+    fn keys(&self) -> Vec<&str> {
+        vec!["StartupNotify"]
+    }
 }
 
 struct StartupWMClassCheck {
@@ -486,6 +683,10 @@ impl Check for StartupWMClassCheck {
     fn check(&self, entries: &HashMap<String, String>) -> bool {
         check_string_entry("StartupWMClass", &self.regex, entries)
     }
+
+    fn keys(&self) -> Vec<&str> {
+        vec!["StartupWMClass"]
+    }
 }
 
 struct URLCheck {
@@ -495,6 +696,10 @@ impl Check for URLCheck {
     fn check(&self, entries: &HashMap<String, String>) -> bool {
         check_string_entry("URL", &self.regex, entries)
     }
+
+    fn keys(&self) -> Vec<&str> {
+        vec!["URL"]
+    }
 }
 
 struct PrefersNonDefaultGPUCheck {}
@@ -502,12 +707,20 @@ impl Check for PrefersNonDefaultGPUCheck {
     fn check(&self, entries: &HashMap<String, String>) -> bool {
         check_entry_true("PrefersNonDefaultGPU", entries)
     }
+
+    fn keys(&self) -> Vec<&str> {
+        vec!["PrefersNonDefaultGPU"]
+    }
 }
 struct NotPrefersNonDefaultGPUCheck {}
 impl Check for NotPrefersNonDefaultGPUCheck {
     fn check(&self, entries: &HashMap<String, String>) -> bool {
         check_entry_false("PrefersNonDefaultGPU", entries)
     }
+
+    fn keys(&self) -> Vec<&str> {
+        vec!["PrefersNonDefaultGPU"]
+    }
 }
 
 fn check_entry_true(key: &str, entries: &HashMap<String, String>) -> bool {
@@ -578,7 +791,7 @@ fn check_localized_entry(
     false
 }
 
-fn prepare_language_strings(lang: String) -> Vec<String> {
+pub fn prepare_language_strings(lang: String) -> Vec<String> {
     let mut language_strings: Vec<String> = Vec::with_capacity(4);
 
     let level1 = Regex::new(r"(.+)_([^.]+)(?:\..+)?@(.+)").unwrap();
@@ -747,6 +960,26 @@ mod tests {
         assert!(checker.check_entry(&entry));
     }
 
+    #[test]
+    fn test_main_category() {
+        let entry = |categories: &str| {
+            let mut entries = HashMap::new();
+            entries.insert(String::from("Categories"), String::from(categories));
+            Entry::from_entries(entries)
+        };
+        let checker = |regex: &str| {
+            Checker::new(Configuration::from_iter(&[
+                APP_NAME,
+                "--main-category",
+                regex,
+            ]))
+        };
+
+        assert!(checker("Network").check_entry(&entry("Qt;WebBrowser;")));
+        assert!(checker("^Other$").check_entry(&entry("Qt;KDE;")));
+        assert!(!checker("Game").check_entry(&entry("Utility;Game;")));
+    }
+
     #[test]
     fn test_localized() {
         let mut entries = HashMap::new();
@@ -878,4 +1111,33 @@ mod tests {
         let checker = Checker::new(conf);
         assert!(checker.check_entry(&entry));
     }
+
+    #[test]
+    fn test_parse_entry() -> Result<(), Box<dyn std::error::Error>> {
+        let contents = "[Desktop Entry]
+Type=Link
+Name=Foo
+Name[de]=Fuu
+Icon=foo
+URL=https://example.com
+I am just an invalid line";
+
+        let conf = Configuration::from_iter(&[APP_NAME, "-a"]);
+        let checker = Checker::new(conf);
+        assert!(checker.parse_entry(contents, Some(&[]))?.is_none());
+
+        let conf = Configuration::from_iter(&[APP_NAME, "-l", "-n", "Foo"]);
+        let checker = Checker::new(conf);
+        assert!(checker.parse_entry(contents, Some(&["Icon"])).is_err());
+
+        let contents = contents.trim_end_matches("I am just an invalid line");
+        let entry = checker.parse_entry(contents, Some(&["Icon"]))?.unwrap();
+        let mut keys: Vec<&String> = entry.get_entries().keys().collect();
+        keys.sort();
+        assert_eq!(keys, vec!["Icon", "Name", "Type"]);
+
+        let entry = checker.parse_entry(contents, None)?.unwrap();
+        assert_eq!(entry.get_entries().len(), 5);
+        Ok(())
+    }
 }
@@ -0,0 +1,522 @@
+use desktopentries::checker;
+use desktopentries::configuration::{
+    AutostartCommand, AutostartEntry, AutostartRun, Command, HideEntry, MenuOptions,
+    MimeAssociation, MimeCommand, MimeQuery, NewEntry, OpenTarget, PruneEntries, RemoveEntry,
+    SetKeys, UnsetKeys, UpdateCache,
+};
+use desktopentries::entry::Entry;
+use desktopentries::history::{self, History};
+use desktopentries::keyfile::{KeyFile, DESKTOP_ENTRY_GROUP};
+use desktopentries::menu;
+use desktopentries::mime::{self, MimeApps};
+use desktopentries::mimedb::{self, MimeDatabase};
+use desktopentries::overrides::{self, EditError};
+use desktopentries::{autostart, create, discovery, exec, open, validate, xdg};
+use std::fs;
+use std::io::{self, BufRead, Write};
+use std::path::{Path, PathBuf};
+use std::process;
+
+/// Runs the subcommand, exiting with an error if it fails.
+pub fn run(command: Command, dirs: &[PathBuf], jobs: usize) {
+    match command {
+        Command::New(options) => new_entry(*options),
+        Command::Set(options) => set_keys(options, dirs, jobs),
+        Command::Unset(options) => unset_keys(options, dirs, jobs),
+        Command::Hide(options) => hide(options, dirs, jobs),
+        Command::Unhide(options) => unhide(options, dirs, jobs),
+        Command::Remove(options) => remove(options, dirs, jobs),
+        Command::Prune(options) => prune(options, jobs),
+        Command::Open(options) => open(options, dirs, jobs),
+        Command::Mime(MimeCommand::Query(options)) => mime_query(options, dirs, jobs),
+        Command::Mime(MimeCommand::SetDefault(options)) => {
+            edit_mimeapps(options, dirs, jobs, true, mime::set_default)
+        }
+        Command::Mime(MimeCommand::Add(options)) => {
+            edit_mimeapps(options, dirs, jobs, true, mime::add_association)
+        }
+        Command::Mime(MimeCommand::Remove(options)) => {
+            edit_mimeapps(options, dirs, jobs, false, mime::remove_association)
+        }
+        Command::Mime(MimeCommand::UpdateCache(options)) => update_cache(options),
+        Command::Autostart(AutostartCommand::Enable(options)) => {
+            autostart_enable(options, dirs, jobs)
+        }
+        Command::Autostart(AutostartCommand::Disable(options)) => autostart_disable(options, jobs),
+        Command::Autostart(AutostartCommand::Run(options)) => autostart_run(options, jobs),
+        Command::Menu(options) => print_menu(options, dirs, jobs),
+    }
+}
+
+/// Generates a new entry, validates it and installs it, printing the path of the installed file.
+fn new_entry(options: NewEntry) {
+    let contents = create::generate(&options);
+    let problems = validate::validate(&contents);
+    if !problems.is_empty() {
+        for problem in problems {
+            eprintln!("{}", problem);
+        }
+        process::exit(1);
+    }
+    for warning in validate::warnings(&contents) {
+        eprintln!("Warning: {}", warning);
+    }
+    if options.print {
+        print!("{}", contents);
+        return;
+    }
+
+    let name = match &options.id {
+        Some(id) => id.clone(),
+        None => create::id_from_name(&options.name),
+    };
+    let vendor = if options.no_vendor {
+        None
+    } else {
+        Some(options.vendor.as_str())
+    };
+    let id = match create::desktop_file_id(vendor, &name) {
+        Ok(id) => id,
+        Err(error) => {
+            eprintln!("{}", error);
+            process::exit(1);
+        }
+    };
+    let dir = match options
+        .dir
+        .clone()
+        .or_else(overrides::user_applications_dir)
+    {
+        Some(dir) => dir,
+        None => {
+            eprintln!("$XDG_DATA_HOME and $HOME not set, specify the directory using --dir");
+            process::exit(1);
+        }
+    };
+
+    match create::install(&dir, &id, &contents, options.force) {
+        Ok(path) => println!("{}", path.display()),
+        Err(error) if error.kind() == io::ErrorKind::AlreadyExists => {
+            eprintln!("{}, use --force to replace it", error);
+            process::exit(1);
+        }
+        Err(error) => {
+            eprintln!("Could not install {}: {}", id, error);
+            process::exit(1);
+        }
+    }
+}
+
+fn set_keys(options: SetKeys, dirs: &[PathBuf], jobs: usize) {
+    let id = overrides::normalize_id(&options.id);
+    let result = overrides::edit(&id, dirs, &user_dir(), jobs, |key_file| {
+        for (key, value) in &options.keys {
+            key_file.set(DESKTOP_ENTRY_GROUP, key, value);
+        }
+        true
+    });
+    report_edit(result);
+}
+
+fn unset_keys(options: UnsetKeys, dirs: &[PathBuf], jobs: usize) {
+    let id = overrides::normalize_id(&options.id);
+    let result = overrides::edit(&id, dirs, &user_dir(), jobs, |key_file| {
+        let mut changed = false;
+        for key in &options.keys {
+            if key_file.unset(DESKTOP_ENTRY_GROUP, key) {
+                changed = true;
+            } else {
+                eprintln!("Key {} is not set", key);
+            }
+        }
+        changed
+    });
+    report_edit(result);
+}
+
+fn hide(options: HideEntry, dirs: &[PathBuf], jobs: usize) {
+    let id = overrides::normalize_id(&options.id);
+    let key = visibility_key(&options);
+    let result = overrides::edit(&id, dirs, &user_dir(), jobs, |key_file| {
+        if key_file.get(DESKTOP_ENTRY_GROUP, key) == Some("true") {
+            return false;
+        }
+        key_file.set(DESKTOP_ENTRY_GROUP, key, "true");
+        true
+    });
+    report_edit(result);
+}
+
+/// Removes the key hiding the entry and removes the override if nothing else was changed in it,
+/// printing the path of the entry in effect.
+fn unhide(options: HideEntry, dirs: &[PathBuf], jobs: usize) {
+    let id = overrides::normalize_id(&options.id);
+    let key = visibility_key(&options);
+    revert(&id, dirs, &user_dir(), jobs, |key_file| {
+        key_file.unset(DESKTOP_ENTRY_GROUP, key)
+    });
+}
+
+/// Edits the entry and removes the override if it no longer differs from the entry it overrides.
+/// Returns the path of the entry in effect, which is printed.
+fn revert<F>(id: &str, dirs: &[PathBuf], user_dir: &Path, jobs: usize, edit: F) -> PathBuf
+where
+    F: FnOnce(&mut KeyFile) -> bool,
+{
+    let path = match overrides::edit(id, dirs, user_dir, jobs, edit) {
+        Ok(path) => path,
+        Err(error) => {
+            eprintln!("{}", error);
+            process::exit(1);
+        }
+    };
+
+    let path = match overrides::remove_redundant(id, dirs, user_dir, jobs) {
+        Ok(overridden) => overridden.unwrap_or(path),
+        Err(error) => {
+            eprintln!("Could not remove {}: {}", path.display(), error);
+            process::exit(1);
+        }
+    };
+    println!("{}", path.display());
+    path
+}
+
+fn remove(options: RemoveEntry, dirs: &[PathBuf], jobs: usize) {
+    let id = overrides::normalize_id(&options.id);
+    report_edit(overrides::remove(&id, dirs, &user_dir(), jobs));
+}
+
+/// Removes the stale entries in the user's directory, asking for every entry unless --yes is
+/// specified. Prints the paths of the removed entries.
+fn prune(options: PruneEntries, jobs: usize) {
+    let user_dir = user_dir();
+    let mut failed = false;
+    for stale in overrides::stale_entries(&user_dir, jobs) {
+        let reason = format!("{} not found", stale.program);
+        if options.dry_run {
+            println!("{}\t{}", stale.path.display(), reason);
+            continue;
+        }
+        if !options.yes && !confirm(&format!("Remove {} ({})?", stale.path.display(), reason)) {
+            continue;
+        }
+        match fs::remove_file(&stale.path) {
+            Ok(()) => println!("{}", stale.path.display()),
+            Err(error) => {
+                eprintln!("Could not remove {}: {}", stale.path.display(), error);
+                failed = true;
+            }
+        }
+    }
+    if failed {
+        process::exit(1);
+    }
+}
+
+/// Asks the question on stderr and reads the answer from stdin, anything but y or yes is a no.
+fn confirm(question: &str) -> bool {
+    eprint!("{} [y/N] ", question);
+    let _ = io::stderr().flush();
+    let mut answer = String::new();
+    if io::stdin().lock().read_line(&mut answer).is_err() {
+        return false;
+    }
+    matches!(answer.trim().to_lowercase().as_str(), "y" | "yes")
+}
+
+/// Prints the ID of the default application for the MIME type, or of all associated applications.
+fn mime_query(options: MimeQuery, dirs: &[PathBuf], jobs: usize) {
+    let database = MimeDatabase::load(&mimedb::mime_dirs(dirs));
+    let mime_apps = MimeApps::load(dirs, jobs);
+    let ids = if options.all {
+        open::find_applications(&options.mime_type, &database, &mime_apps)
+    } else {
+        open::find_application(&options.mime_type, &database, &mime_apps)
+            .into_iter()
+            .collect()
+    };
+    if ids.is_empty() {
+        eprintln!("No application found for {}", options.mime_type);
+        process::exit(1);
+    }
+    for id in ids {
+        println!("{}", id);
+    }
+}
+
+/// Edits the user's mimeapps.list, printing its path. The application must be installed unless
+/// its association is removed.
+fn edit_mimeapps<F>(
+    options: MimeAssociation,
+    dirs: &[PathBuf],
+    jobs: usize,
+    require_installed: bool,
+    edit: F,
+) where
+    F: FnOnce(&mut KeyFile, &str, &str),
+{
+    let id = overrides::normalize_id(&options.id);
+    if require_installed && !discovery::get_entries_from_dirs(dirs, jobs).contains_key(&id) {
+        eprintln!("Could not find entry {}", id);
+        process::exit(1);
+    }
+    let path = match mime::user_mimeapps_path() {
+        Some(path) => path,
+        None => {
+            eprintln!("$XDG_CONFIG_HOME and $HOME not set, could not determine mimeapps.list");
+            process::exit(1);
+        }
+    };
+
+    match mime::edit_mimeapps(&path, |key_file| edit(key_file, &options.mime_type, &id)) {
+        Ok(_) => println!("{}", path.display()),
+        Err(error) => {
+            eprintln!("Could not write {}: {}", path.display(), error);
+            process::exit(1);
+        }
+    }
+}
+
+/// Writes the mimeinfo.cache of the directories, printing the paths of the written files. In check
+/// mode the directories whose cache is stale are printed instead.
+fn update_cache(options: UpdateCache) {
+    let mut failed = false;
+    for dir in &options.dirs {
+        if options.check {
+            if mime::is_cache_stale(dir) {
+                println!("{}", dir.display());
+                failed = true;
+            }
+            continue;
+        }
+        match mime::update_cache(dir) {
+            Ok(path) => println!("{}", path.display()),
+            Err(error) => {
+                eprintln!("Could not write the cache of {}: {}", dir.display(), error);
+                failed = true;
+            }
+        }
+    }
+    if failed {
+        process::exit(1);
+    }
+}
+
+/// Opens the file or URL and records the launch of the application in the history.
+fn open(options: OpenTarget, dirs: &[PathBuf], jobs: usize) {
+    let language_strings = language_strings(options.lang);
+    let id = match open::open(&options.target, dirs, &language_strings, jobs) {
+        Ok(id) => id,
+        Err(error) => {
+            eprintln!("{}", error);
+            process::exit(1);
+        }
+    };
+
+    let mut history = History::load_default();
+    history.record(&id, history::now());
+    if let Err(error) = history.save() {
+        eprintln!("Could not save the launch history: {}", error);
+    }
+}
+
+/// Enables the entry in the autostart directories, or copies the application with the ID into the
+/// user's autostart directory if there is no such entry. Warns if the entry is still not started
+/// at login.
+fn autostart_enable(options: AutostartEntry, dirs: &[PathBuf], jobs: usize) {
+    let id = overrides::normalize_id(&options.id);
+    let user_dir = user_autostart_dir();
+    let autostart_dirs = autostart::autostart_dirs();
+
+    let path = match discovery::get_entries_from_dirs(&autostart_dirs, jobs).remove(&id) {
+        Some(path) if !is_disabled(&path) => {
+            println!("{}", path.display());
+            path
+        }
+        Some(_) => revert(&id, &autostart_dirs, &user_dir, jobs, |key_file| {
+            let hidden = key_file.unset(DESKTOP_ENTRY_GROUP, "Hidden");
+            let enabled = key_file.unset(DESKTOP_ENTRY_GROUP, autostart::GNOME_ENABLED_KEY);
+            hidden || enabled
+        }),
+        None => copy_to_autostart(&id, dirs, &user_dir, jobs),
+    };
+
+    let entry = fs::read_to_string(&path)
+        .ok()
+        .and_then(|contents| Entry::new(&contents).ok());
+    if let Some(reason) =
+        entry.and_then(|entry| autostart::skip_reason(&entry, &xdg::current_desktops()))
+    {
+        eprintln!("{} is still not started at login: {}", id, reason);
+    }
+}
+
+/// Returns whether the autostart entry is disabled by Hidden or X-GNOME-Autostart-enabled.
+fn is_disabled(path: &Path) -> bool {
+    let key_file = KeyFile::parse(&fs::read_to_string(path).unwrap_or_default());
+    key_file.get(DESKTOP_ENTRY_GROUP, "Hidden") == Some("true")
+        || key_file.get(DESKTOP_ENTRY_GROUP, autostart::GNOME_ENABLED_KEY) == Some("false")
+}
+
+/// Copies the application into the user's autostart directory, printing the path of the copy.
+fn copy_to_autostart(id: &str, dirs: &[PathBuf], user_dir: &Path, jobs: usize) -> PathBuf {
+    let source = match discovery::get_entries_from_dirs(dirs, jobs).remove(id) {
+        Some(source) => source,
+        None => {
+            eprintln!("Could not find entry {}", id);
+            process::exit(1);
+        }
+    };
+    let installed = fs::read_to_string(&source)
+        .and_then(|contents| create::install(user_dir, id, &contents, false));
+    match installed {
+        Ok(path) => {
+            println!("{}", path.display());
+            path
+        }
+        Err(error) => {
+            eprintln!("Could not copy {}: {}", source.display(), error);
+            process::exit(1);
+        }
+    }
+}
+
+/// Disables the entry by overriding it with Hidden=true in the user's autostart directory.
+fn autostart_disable(options: AutostartEntry, jobs: usize) {
+    let id = overrides::normalize_id(&options.id);
+    let autostart_dirs = autostart::autostart_dirs();
+    let result = overrides::edit(
+        &id,
+        &autostart_dirs,
+        &user_autostart_dir(),
+        jobs,
+        |key_file| {
+            if key_file.get(DESKTOP_ENTRY_GROUP, "Hidden") == Some("true") {
+                return false;
+            }
+            key_file.set(DESKTOP_ENTRY_GROUP, "Hidden", "true");
+            true
+        },
+    );
+    report_edit(result);
+}
+
+/// Starts the effective autostart entries in the order of their IDs, or prints their command lines
+/// prefixed by their IDs and a tab in dry-run mode. Entries which cannot be started are logged and
+/// skipped.
+fn autostart_run(options: AutostartRun, jobs: usize) {
+    let desktops = if options.desktops.is_empty() {
+        xdg::current_desktops()
+    } else {
+        options.desktops
+    };
+    let language_strings = language_strings(options.lang);
+    let (entries, errors) =
+        autostart::effective_entries(&autostart::autostart_dirs(), &desktops, jobs);
+    let mut failed = !errors.is_empty();
+    for error in errors {
+        eprintln!("{}", error);
+    }
+
+    for matched in entries {
+        let result = if options.dry_run {
+            exec::expand_exec(&matched.entry, &matched.path, &[], &language_strings).map(
+                |commands| {
+                    for command in commands {
+                        println!("{}\t{}", matched.id, exec::command_line(&command));
+                    }
+                },
+            )
+        } else {
+            exec::launch(&matched.entry, &matched.path, &[], &language_strings)
+        };
+        if let Err(error) = result {
+            eprintln!("Could not start {}: {}", matched.path.display(), error);
+            failed = true;
+        }
+    }
+    if failed {
+        process::exit(1);
+    }
+}
+
+/// Returns the language strings of the specified language, or of $LC_MESSAGES, used to look up
+/// the localized keys the same way as the queries do.
+fn language_strings(lang: Option<String>) -> Vec<String> {
+    lang.or_else(|| std::env::var("LC_MESSAGES").ok())
+        .map(checker::prepare_language_strings)
+        .unwrap_or_default()
+}
+
+/// Builds the application menu and prints it in the requested format.
+fn print_menu(options: MenuOptions, dirs: &[PathBuf], jobs: usize) {
+    let path = match options.file.or_else(menu::menu_file) {
+        Some(path) => path,
+        None => {
+            eprintln!("No applications menu found, specify the menu file using --file");
+            process::exit(1);
+        }
+    };
+    let desktops = if options.desktops.is_empty() {
+        xdg::current_desktops()
+    } else {
+        options.desktops
+    };
+    let language_strings = language_strings(options.lang);
+
+    let menu = match menu::load(&path, dirs, &desktops, &language_strings, jobs) {
+        Ok(menu) => menu,
+        Err(error) => {
+            eprintln!("{}", error);
+            process::exit(1);
+        }
+    };
+    let stdout = io::stdout();
+    let result = menu::write_menu(&menu, options.format, &mut stdout.lock());
+    if let Err(error) = result {
+        eprintln!("Could not print the menu: {}", error);
+        process::exit(1);
+    }
+}
+
+fn user_autostart_dir() -> PathBuf {
+    match autostart::user_autostart_dir() {
+        Some(dir) => dir,
+        None => {
+            eprintln!(
+                "$XDG_CONFIG_HOME and $HOME not set, could not determine the autostart directory"
+            );
+            process::exit(1);
+        }
+    }
+}
+
+fn visibility_key(options: &HideEntry) -> &'static str {
+    if options.no_display {
+        "NoDisplay"
+    } else {
+        "Hidden"
+    }
+}
+
+fn user_dir() -> PathBuf {
+    match overrides::user_applications_dir() {
+        Some(dir) => dir,
+        None => {
+            eprintln!("$XDG_DATA_HOME and $HOME not set, could not determine the user's directory");
+            process::exit(1);
+        }
+    }
+}
+
+/// Prints the path of the edited file, or the error.
+fn report_edit(result: Result<PathBuf, EditError>) {
+    match result {
+        Ok(path) => println!("{}", path.display()),
+        Err(error) => {
+            eprintln!("{}", error);
+            process::exit(1);
+        }
+    }
+}
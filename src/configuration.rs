@@ -1,4 +1,10 @@
+use crate::create::parse_key_value;
+use crate::discovery::DataDirMode;
+use crate::menu::{MenuFormat, MENU_FORMATS};
+use crate::output::Format;
+use crate::sort::SortKey;
 use regex::Regex;
+use std::path::PathBuf;
 use structopt::StructOpt;
 
 #[derive(Debug, StructOpt)]
@@ -6,12 +12,16 @@ use structopt::StructOpt;
     name = "desktopentries",
     about = "A simple tool for displaying and querying desktop entries",
     after_help = "This tool tries to follow the Desktop Entry Specification. It looks for the \
-    entries in directories specified by $XDG_DATA_DIRS environment variable. If the variable is \
-    not present, the tool looks for the entries in /usr/share/applications/ directory.\n\n\
+    entries in directories specified by $XDG_DATA_HOME and $XDG_DATA_DIRS environment variables, \
+    the former defaulting to ~/.local/share. If $XDG_DATA_DIRS is not present, the tool looks for \
+    the entries in /usr/share/applications/ directory.\n\n\
     If multiple entry files with the same ID exist, the one located in the first entry directory \
-    will be chosen. If the files exist in the same entry directory, the one which was detected \
-    first by Rust's std::fs::read_dir() will be used (default behaviour is undefined by the \
+    will be chosen. If the files exist in the same entry directory, the one found first when \
+    scanning the directory in alphabetical order will be used (default behaviour is undefined by the \
     specification).\n\n\
+    With -d, the desktop-directories subdirectories of the data directories are searched as well, \
+    finding the .directory files used by menus. Their IDs are their paths relative to the \
+    desktop-directories directory.\n\n\
     The output of this tool consists of the paths to the desktop entry files with their contents \
     which match the specified flags and options. To extract particular lines from the output, you \
     can use a tool such as grep and pipe the output of this tool to it.\n\n\
@@ -24,7 +34,83 @@ use structopt::StructOpt;
     The tool also supports localized keys as well. When the -g flag is provided, the tool \
     looks for the value of $LC_MESSAGES environment variable and uses it for searching. You can \
     specify a different language using the -G option, however, it does not check whether the value \
-    specified is a correct locale code."
+    specified is a correct locale code.\n\n\
+    Instead of the data directories, the tool can query the desktop entry files and directories \
+    specified as arguments, f.e. to check freshly built entries before they are installed. \
+    Directories are scanned the same way as the data directories, - reads an entry from stdin. \
+    The tool exits with an error if any of the specified entries could not be read or parsed. \
+    Separate the arguments from options taking multiple values using --.\n\n\
+    Additional data directories can be specified using the --data-dir option, applications \
+    directories using the --applications-dir option. They take precedence over the directories \
+    specified by $XDG_DATA_DIRS in the order they were specified. The --data-dir-mode option \
+    appends them to these directories instead, or replaces these directories completely.\n\n\
+    The menu, dmenu and rofi formats print one line per entry which can be fed to an application \
+    picker, the dmenu format suits any picker reading plain lines such as wofi --dmenu. The line \
+    chosen in the picker can be passed back using the --launch-id option, which launches the \
+    corresponding entry by running its Exec key, D-Bus activation is not supported.\n\n\
+    The entries are ordered by their IDs unless a different order is specified by the --sort \
+    option. Sorting by name uses the localized Name key when the search is localized. Names and \
+    other values are collated according to $LC_ALL, $LC_COLLATE or $LANG.\n\n\
+    The --search option matches the term fuzzily against the Name, GenericName, Keywords and \
+    Comment keys, the executable and the ID of the entries. The entries are ordered by their \
+    scores, which are printed by the tsv format, unless a different order is specified.\n\n\
+    Entries launched using the --launch-id option are recorded in a history stored in \
+    $XDG_STATE_HOME/desktopentries/history. The history is used to order the entries by their \
+    frecency, a combination of how often and how recently they were launched, and to boost the \
+    scores of frequently used entries when searching.\n\n\
+    The --lazy option speeds up queries over many entries by storing only the keys needed by the \
+    checks, the output format, the search and the sort order, and by skipping the rest of an entry \
+    as soon as it fails a check. Entries are parsed completely when launching.\n\n\
+    The --watch option keeps the tool running after printing the matching entries. When entries \
+    are added, changed or removed in any of the directories or their subdirectories, the \
+    directories are scanned again once nothing changed for the --debounce time, and every entry \
+    which started or stopped matching the query, or whose contents or precedence changed, is \
+    printed prefixed by added, changed or removed and a tab.\n\n\
+    The --serve option starts a server which keeps the entries in memory, watches the directories \
+    and answers queries sent to a Unix socket. Every query is a line of JSON containing the \
+    arguments of the query, f.e. {\"args\": [\"-a\", \"--search\", \"fire\"]}, answered by a \
+    line of JSON containing the matching entries. The --client option sends the other arguments \
+    to the server and prints the answer the same way as without the server.\n\n\
+    The new subcommand creates an entry from the specified keys, validates it and installs it \
+    into $XDG_DATA_HOME/applications under a desktop file ID prefixed by a vendor, run \
+    desktopentries new --help for its options.\n\n\
+    The set and unset subcommands edit the keys of an installed entry. An entry which is not \
+    located in $XDG_DATA_HOME/applications is copied there first, so that the copy overrides it \
+    and the original file is left untouched. Comments and the order of the keys are preserved.\n\n\
+    The hide subcommand hides an entry for the current user by setting Hidden, or NoDisplay if \
+    --no-display is specified, the same way. The unhide subcommand reverts it and removes the \
+    override if it no longer differs from the entry it overrides.\n\n\
+    The remove subcommand removes an entry or override from $XDG_DATA_HOME/applications, the prune \
+    subcommand finds the applications there whose TryExec or Exec program no longer exists and \
+    asks whether to remove them.\n\n\
+    The mime query subcommand prints the default application for a MIME type, resolved from the \
+    mimeapps.list files in $XDG_CONFIG_HOME, $XDG_CONFIG_DIRS and the applications directories, \
+    including the ones specific to $XDG_CURRENT_DESKTOP, falling back to mimeinfo.cache. The \
+    mime set-default, add and remove subcommands edit $XDG_CONFIG_HOME/mimeapps.list, preserving \
+    its other groups and keys.\n\n\
+    The open subcommand opens a file or URL with its default application. The MIME type of a file \
+    is detected by the globs2 and magic files of the shared-mime-info database, URLs are opened \
+    by the handler of their scheme, x-scheme-handler/scheme.\n\n\
+    The mime update-cache subcommand writes the mimeinfo.cache of applications directories the \
+    same way as update-desktop-database, --check only reports the directories whose cache is \
+    missing or stale.\n\n\
+    The --autostart option queries the autostart directories instead of the applications \
+    directories and shows only the entries started at login, applying Hidden, OnlyShowIn, \
+    NotShowIn, TryExec and X-GNOME-Autostart-enabled. The autostart enable and disable \
+    subcommands write overrides into $XDG_CONFIG_HOME/autostart, autostart run starts the \
+    entries started at login in the order of their IDs.\n\n\
+    The menu subcommand prints the application menu described by \
+    $XDG_CONFIG_DIRS/menus/${XDG_MENU_PREFIX}applications.menu as specified by the Desktop Menu \
+    Specification, the same tree GNOME and KDE show, either as an indented tree, as JSON or as \
+    the menu configuration of a window manager.\n\n\
+    For window managers without menu files, --category-menu groups the matching entries by the \
+    first registered main category of their Categories keys. Additional categories are grouped \
+    under their related main category, entries without any under Other. The --main-category \
+    option filters the entries by the same main category.\n\n\
+    The new, set, unset, hide and unhide subcommands warn about Categories values which are not \
+    registered by the Desktop Menu Specification, additional categories without their related \
+    categories and values without a main category. The --warn option reports the same warnings \
+    for the matching entries, f.e. to check entry files in packaging scripts."
 )]
 pub struct Configuration {
     #[structopt(
@@ -58,9 +144,9 @@ pub struct Configuration {
         name = "Link",
         conflicts_with_all = &[
             "Not Link", "Directory", "TryExec", "Exec", "Path", "Terminal", "Not Terminal",
-            "Actions", "MimeType", "Categories", "Implements", "Keywords", "StartupNotify",
-            "Not StartupNotify", "StartupWMClass", "Not StartupWMClass", "PrefersNonDefaultGPU",
-            "Not PrefersNonDefaultGPU"
+            "Actions", "MimeType", "Categories", "MainCategory", "Implements", "Keywords",
+            "StartupNotify", "Not StartupNotify", "StartupWMClass", "Not StartupWMClass",
+            "PrefersNonDefaultGPU", "Not PrefersNonDefaultGPU"
         ],
         help = "Shows only entries where Type is Link",
     display_order = 2
@@ -72,9 +158,9 @@ pub struct Configuration {
         name = "Directory",
         conflicts_with_all = &[
             "Not Directory", "URL", "TryExec", "Exec", "Path", "Terminal", "Not Terminal",
-            "Actions", "MimeType", "Categories", "Implements", "Keywords", "StartupNotify",
-            "Not StartupNotify", "StartupWMClass", "Not StartupWMClass", "PrefersNonDefaultGPU",
-            "Not PrefersNonDefaultGPU"
+            "Actions", "MimeType", "Categories", "MainCategory", "Implements", "Keywords",
+            "StartupNotify", "Not StartupNotify", "StartupWMClass", "Not StartupWMClass",
+            "PrefersNonDefaultGPU", "Not PrefersNonDefaultGPU"
         ],
         help = "Shows only entries where Type is Directory",
     display_order = 3
@@ -87,8 +173,8 @@ pub struct Configuration {
         name = "Not Application",
         conflicts_with_all = &[
             "TryExec", "Exec", "Path", "Terminal", "Not Terminal", "Actions", "MimeType",
-            "Categories", "Implements", "Keywords", "StartupNotify", "Not StartupNotify",
-            "StartupWMClass", "Not StartupWMClass", "PrefersNonDefaultGPU",
+            "Categories", "MainCategory", "Implements", "Keywords", "StartupNotify",
+            "Not StartupNotify", "StartupWMClass", "Not StartupWMClass", "PrefersNonDefaultGPU",
             "Not PrefersNonDefaultGPU"
         ],
         help = "Shows only entries where Type is not Application",
@@ -300,6 +386,15 @@ pub struct Configuration {
         display_order = 27
     )]
     pub categories: Option<Vec<Regex>>,
+    #[structopt(
+        long = "main-category",
+        name = "MainCategory",
+        help = "Shows only entries whose main category matches specified value, the first \
+        registered main category of Categories or the one related to its first additional \
+        category, Other if there is none",
+        display_order = 58
+    )]
+    pub main_category: Option<Regex>,
 
     #[structopt(
         short = "I",
@@ -352,8 +447,8 @@ pub struct Configuration {
         name = "URL",
         conflicts_with_all = &[
             "TryExec", "Exec", "Path", "Terminal", "Not Terminal", "Actions", "MimeType",
-            "Categories", "Implements", "Keywords", "StartupNotify", "Not StartupNotify",
-            "StartupWMClass", "Not StartupWMClass", "PrefersNonDefaultGPU",
+            "Categories", "MainCategory", "Implements", "Keywords", "StartupNotify",
+            "Not StartupNotify", "StartupWMClass", "Not StartupWMClass", "PrefersNonDefaultGPU",
             "Not PrefersNonDefaultGPU"
         ],
         help = "Shows only entries where URL matches specified value",
@@ -378,4 +473,738 @@ pub struct Configuration {
         display_order = 35
     )]
     pub not_non_default_gpu: bool,
+
+    #[structopt(
+        short = "f",
+        long = "format",
+        name = "Format",
+        default_value = "entries",
+        possible_values = &["entries", "menu", "dmenu", "rofi", "tsv"],
+        help = "Sets the output format: entries prints paths with contents, menu prints labels \
+        with a tab separated ID, dmenu prints labels only, rofi prints labels with icon hints and tsv \
+        prints tab separated IDs, paths, labels and search scores",
+        display_order = 36
+    )]
+    pub format: Format,
+    #[structopt(
+        long = "with-generic-name",
+        name = "WithGenericName",
+        help = "Appends GenericName to the labels printed by the menu, dmenu and rofi formats",
+        display_order = 37
+    )]
+    pub with_generic_name: bool,
+    #[structopt(
+        long = "launch-id",
+        name = "LaunchID",
+        help = "Launches the matching entry identified by a line printed by the menu, dmenu or \
+        rofi format, the line is read from stdin if set to -",
+        display_order = 38
+    )]
+    pub launch_id: Option<String>,
+
+    #[structopt(
+        long = "sort",
+        name = "Sort",
+        help = "Sorts the entries by id, name, path, mtime, score, frecency or the value of any \
+        other specified key",
+        display_order = 39
+    )]
+    pub sort: Option<SortKey>,
+    #[structopt(
+        long = "reverse",
+        name = "Reverse",
+        help = "Reverses the order of the entries",
+        display_order = 40
+    )]
+    pub reverse: bool,
+    #[structopt(
+        long = "limit",
+        name = "Limit",
+        help = "Shows at most the specified number of entries",
+        display_order = 41
+    )]
+    pub limit: Option<usize>,
+    #[structopt(
+        long = "count",
+        name = "Count",
+        conflicts_with = "LaunchID",
+        help = "Shows only the number of matching entries",
+        display_order = 42
+    )]
+    pub count: bool,
+
+    #[structopt(
+        long = "search",
+        name = "Search",
+        help = "Shows only entries fuzzily matching specified term, ordered by relevance",
+        display_order = 43
+    )]
+    pub search: Option<String>,
+
+    #[structopt(
+        name = "FILE",
+        parse(from_os_str),
+        help = "Desktop entry files or directories to query instead of the data directories, \
+        - reads an entry from stdin"
+    )]
+    pub files: Vec<PathBuf>,
+
+    #[structopt(
+        long = "data-dir",
+        name = "DataDir",
+        number_of_values = 1,
+        conflicts_with = "FILE",
+        parse(from_os_str),
+        help = "Adds a data directory whose applications subdirectory is searched for entries, \
+        can be specified multiple times",
+        display_order = 44
+    )]
+    pub data_dirs: Vec<PathBuf>,
+    #[structopt(
+        long = "applications-dir",
+        name = "ApplicationsDir",
+        number_of_values = 1,
+        conflicts_with = "FILE",
+        parse(from_os_str),
+        help = "Adds a directory which is searched for entries, can be specified multiple times",
+        display_order = 45
+    )]
+    pub applications_dirs: Vec<PathBuf>,
+    #[structopt(
+        long = "data-dir-mode",
+        name = "DataDirMode",
+        default_value = "prepend",
+        possible_values = &["prepend", "append", "replace"],
+        help = "Sets whether the specified directories are prepended or appended to the ones \
+        specified by $XDG_DATA_DIRS, or replace them",
+        display_order = 46
+    )]
+    pub data_dir_mode: DataDirMode,
+
+    #[structopt(
+        short = "j",
+        long = "jobs",
+        name = "Jobs",
+        help = "Sets the number of threads used to scan and parse the entries, defaults to the \
+        number of available CPUs",
+        display_order = 47
+    )]
+    pub jobs: Option<usize>,
+
+    #[structopt(
+        long = "cache",
+        name = "Cache",
+        help = "Uses an index of the entries stored in $XDG_CACHE_HOME/desktopentries, reading \
+        only the entries which changed since the last run",
+        display_order = 48
+    )]
+    pub cache: bool,
+
+    #[structopt(
+        long = "lazy",
+        name = "Lazy",
+        conflicts_with = "Cache",
+        help = "Parses only the keys needed by the query and skips the rest of an entry as soon as \
+        it fails a check, parse errors in the skipped lines are not reported",
+        display_order = 49
+    )]
+    pub lazy: bool,
+
+    #[structopt(
+        long = "watch",
+        name = "Watch",
+        conflicts_with_all = &["LaunchID", "Count", "FILE"],
+        help = "Prints the matching entries, then watches the directories and prints the added, \
+        changed and removed entries",
+        display_order = 50
+    )]
+    pub watch: bool,
+
+    #[structopt(
+        long = "debounce",
+        name = "Debounce",
+        default_value = "200",
+        help = "Sets the time in milliseconds without changes after which the directories are \
+        scanned again when watching",
+        display_order = 51
+    )]
+    pub debounce: u64,
+
+    #[structopt(
+        long = "serve",
+        name = "Serve",
+        conflicts_with_all = &["LaunchID", "Count", "FILE", "Watch", "Lazy", "Client"],
+        help = "Keeps the matching entries in memory and answers queries sent to the socket, \
+        reloading the entries when the directories change",
+        display_order = 52
+    )]
+    pub serve: bool,
+
+    #[structopt(
+        long = "client",
+        name = "Client",
+        conflicts_with_all = &["FILE", "DataDir", "ApplicationsDir", "Cache", "Watch"],
+        help = "Sends the query to a running server instead of reading the entries",
+        display_order = 53
+    )]
+    pub client: bool,
+
+    #[structopt(
+        long = "socket",
+        name = "Socket",
+        parse(from_os_str),
+        help = "Sets the socket used by the server and the client, defaults to \
+        $XDG_RUNTIME_DIR/desktopentries.sock",
+        display_order = 54
+    )]
+    pub socket: Option<PathBuf>,
+    #[structopt(
+        long = "autostart",
+        name = "Autostart",
+        conflicts_with_all = &["FILE", "DataDir", "ApplicationsDir"],
+        help = "Queries the autostart directories $XDG_CONFIG_HOME/autostart and \
+        $XDG_CONFIG_DIRS/autostart instead of the applications directories, showing only the \
+        entries which are started at login in the desktop environments of $XDG_CURRENT_DESKTOP",
+        display_order = 55
+    )]
+    pub autostart: bool,
+    #[structopt(
+        long = "category-menu",
+        name = "CategoryMenu",
+        possible_values = MENU_FORMATS,
+        conflicts_with_all = &["LaunchID", "Count", "Watch", "Serve"],
+        help = "Prints the matching entries as a menu grouped by their main categories instead, \
+        in the tree, json, openbox, jwm, fluxbox, icewm or twm format",
+        display_order = 56
+    )]
+    pub category_menu: Option<MenuFormat>,
+    #[structopt(
+        long = "warn",
+        name = "Warn",
+        conflicts_with = "Serve",
+        help = "Reports warnings about the matching entries, such as Categories values which are \
+        not registered, making the exit status non-zero if files were specified",
+        display_order = 57
+    )]
+    pub warn: bool,
+
+    #[structopt(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Debug, StructOpt)]
+pub enum Command {
+    #[structopt(
+        name = "new",
+        about = "Creates a new desktop entry, validates it and installs it into \
+        $XDG_DATA_HOME/applications"
+    )]
+    New(Box<NewEntry>),
+    #[structopt(
+        name = "set",
+        about = "Sets keys of an entry, copying the entry into $XDG_DATA_HOME/applications first \
+        unless it is located there, so that the copy overrides it"
+    )]
+    Set(SetKeys),
+    #[structopt(
+        name = "unset",
+        about = "Removes keys from an entry, copying the entry into $XDG_DATA_HOME/applications \
+        first unless it is located there, so that the copy overrides it"
+    )]
+    Unset(UnsetKeys),
+    #[structopt(
+        name = "hide",
+        about = "Hides an entry for the current user by overriding it with Hidden=true, or with \
+        NoDisplay=true if --no-display is specified"
+    )]
+    Hide(HideEntry),
+    #[structopt(
+        name = "unhide",
+        about = "Reverts hide, removing the override if it no longer differs from the entry it \
+        overrides"
+    )]
+    Unhide(HideEntry),
+    #[structopt(
+        name = "remove",
+        about = "Removes an entry or override from $XDG_DATA_HOME/applications, entries installed \
+        in other directories are never removed"
+    )]
+    Remove(RemoveEntry),
+    #[structopt(
+        name = "prune",
+        about = "Finds the applications in $XDG_DATA_HOME/applications whose TryExec or Exec \
+        program no longer exists and asks whether to remove them"
+    )]
+    Prune(PruneEntries),
+    #[structopt(
+        name = "mime",
+        about = "Resolves the applications associated with MIME types as described by the XDG \
+        MIME Applications Specification"
+    )]
+    Mime(MimeCommand),
+    #[structopt(
+        name = "open",
+        about = "Opens a file or URL with the default application for its MIME type, detected \
+        using the shared-mime-info database, or for the scheme of the URL"
+    )]
+    Open(OpenTarget),
+    #[structopt(
+        name = "autostart",
+        about = "Enables or disables entries started at login by writing overrides into \
+        $XDG_CONFIG_HOME/autostart"
+    )]
+    Autostart(AutostartCommand),
+    #[structopt(
+        name = "menu",
+        about = "Prints the application menu built from the menu files of the Desktop Menu \
+        Specification and the discovered entries"
+    )]
+    Menu(MenuOptions),
+}
+
+#[derive(Debug, StructOpt)]
+pub enum AutostartCommand {
+    #[structopt(
+        name = "enable",
+        about = "Enables an autostart entry, or starts an application at login by copying its \
+        entry into $XDG_CONFIG_HOME/autostart"
+    )]
+    Enable(AutostartEntry),
+    #[structopt(
+        name = "disable",
+        about = "Disables an autostart entry by overriding it with Hidden=true"
+    )]
+    Disable(AutostartEntry),
+    #[structopt(
+        name = "run",
+        about = "Starts the autostart entries for the current desktop environments in the order \
+        of their IDs, logging the entries which could not be started"
+    )]
+    Run(AutostartRun),
+}
+
+#[derive(Debug, StructOpt)]
+pub enum MimeCommand {
+    #[structopt(
+        name = "query",
+        about = "Prints the desktop file ID of the default application for the MIME type"
+    )]
+    Query(MimeQuery),
+    #[structopt(
+        name = "set-default",
+        about = "Makes the application the default one for the MIME type in \
+        $XDG_CONFIG_HOME/mimeapps.list"
+    )]
+    SetDefault(MimeAssociation),
+    #[structopt(
+        name = "add",
+        about = "Associates the application with the MIME type in $XDG_CONFIG_HOME/mimeapps.list"
+    )]
+    Add(MimeAssociation),
+    #[structopt(
+        name = "remove",
+        about = "Removes the association of the application with the MIME type in \
+        $XDG_CONFIG_HOME/mimeapps.list"
+    )]
+    Remove(MimeAssociation),
+    #[structopt(
+        name = "update-cache",
+        about = "Writes the mimeinfo.cache of applications directories in the format of \
+        update-desktop-database"
+    )]
+    UpdateCache(UpdateCache),
+}
+
+#[derive(Debug, StructOpt)]
+pub struct NewEntry {
+    #[structopt(
+        long = "type",
+        name = "Type",
+        default_value = "Application",
+        possible_values = &["Application", "Link", "Directory"],
+        help = "Sets the Type key",
+        display_order = 1
+    )]
+    pub entry_type: String,
+    #[structopt(
+        long = "name",
+        name = "Name",
+        help = "Sets the Name key",
+        display_order = 2
+    )]
+    pub name: String,
+    #[structopt(
+        long = "generic-name",
+        name = "GenericName",
+        help = "Sets the GenericName key",
+        display_order = 3
+    )]
+    pub generic_name: Option<String>,
+    #[structopt(
+        long = "comment",
+        name = "Comment",
+        help = "Sets the Comment key",
+        display_order = 4
+    )]
+    pub comment: Option<String>,
+    #[structopt(
+        long = "icon",
+        name = "Icon",
+        help = "Sets the Icon key",
+        display_order = 5
+    )]
+    pub icon: Option<String>,
+    #[structopt(
+        long = "exec",
+        name = "Exec",
+        help = "Sets the Exec key, quoted as described by the specification",
+        display_order = 6
+    )]
+    pub exec: Option<String>,
+    #[structopt(
+        long = "try-exec",
+        name = "TryExec",
+        help = "Sets the TryExec key",
+        display_order = 7
+    )]
+    pub try_exec: Option<String>,
+    #[structopt(
+        long = "path",
+        name = "Path",
+        help = "Sets the Path key",
+        display_order = 8
+    )]
+    pub path: Option<String>,
+    #[structopt(
+        long = "terminal",
+        name = "Terminal",
+        help = "Sets the Terminal key to true",
+        display_order = 9
+    )]
+    pub terminal: bool,
+    #[structopt(
+        long = "url",
+        name = "URL",
+        help = "Sets the URL key",
+        display_order = 10
+    )]
+    pub url: Option<String>,
+    #[structopt(
+        long = "categories",
+        name = "Categories",
+        min_values = 1,
+        help = "Sets the Categories key, the values can also be separated by semicolons",
+        display_order = 11
+    )]
+    pub categories: Vec<String>,
+    #[structopt(
+        long = "keywords",
+        name = "Keywords",
+        min_values = 1,
+        help = "Sets the Keywords key, the values can also be separated by semicolons",
+        display_order = 12
+    )]
+    pub keywords: Vec<String>,
+    #[structopt(
+        long = "mime-type",
+        name = "MimeType",
+        min_values = 1,
+        help = "Sets the MimeType key, the values can also be separated by semicolons",
+        display_order = 13
+    )]
+    pub mime_types: Vec<String>,
+    #[structopt(
+        long = "no-display",
+        name = "NoDisplay",
+        help = "Sets the NoDisplay key to true",
+        display_order = 14
+    )]
+    pub no_display: bool,
+    #[structopt(
+        long = "startup-notify",
+        name = "StartupNotify",
+        help = "Sets the StartupNotify key to true",
+        display_order = 15
+    )]
+    pub startup_notify: bool,
+    #[structopt(
+        long = "startup-wm-class",
+        name = "StartupWMClass",
+        help = "Sets the StartupWMClass key",
+        display_order = 16
+    )]
+    pub startup_wm_class: Option<String>,
+    #[structopt(
+        long = "set",
+        name = "KEY=VALUE",
+        number_of_values = 1,
+        parse(try_from_str = parse_key_value),
+        help = "Sets any other key, f.e. a localized Name[de]=Name, the value is written as \
+        specified, can be specified multiple times",
+        display_order = 17
+    )]
+    pub keys: Vec<(String, String)>,
+
+    #[structopt(
+        long = "vendor",
+        name = "Vendor",
+        default_value = "desktopentries",
+        help = "Sets the vendor prefix of the desktop file ID",
+        display_order = 18
+    )]
+    pub vendor: String,
+    #[structopt(
+        long = "no-vendor",
+        name = "NoVendor",
+        help = "Installs the entry without a vendor prefix",
+        display_order = 19
+    )]
+    pub no_vendor: bool,
+    #[structopt(
+        long = "id",
+        name = "ID",
+        help = "Sets the name of the desktop file ID following the vendor prefix, defaults to the \
+        lowercase Name with dashes instead of other characters than ASCII letters and digits. \
+        The ID may contain only ASCII letters, digits, dashes, underscores and dots separating \
+        components which do not start with a digit",
+        display_order = 20
+    )]
+    pub id: Option<String>,
+    #[structopt(
+        long = "dir",
+        name = "Dir",
+        parse(from_os_str),
+        help = "Installs the entry into the directory instead of $XDG_DATA_HOME/applications",
+        display_order = 21
+    )]
+    pub dir: Option<PathBuf>,
+    #[structopt(
+        long = "print",
+        name = "Print",
+        conflicts_with_all = &["Dir", "Force"],
+        help = "Prints the entry instead of installing it",
+        display_order = 22
+    )]
+    pub print: bool,
+    #[structopt(
+        long = "force",
+        name = "Force",
+        help = "Replaces an already installed entry with the same ID",
+        display_order = 23
+    )]
+    pub force: bool,
+}
+
+#[derive(Debug, StructOpt)]
+pub struct SetKeys {
+    #[structopt(
+        name = "ID",
+        help = "Sets the desktop file ID of the entry, the .desktop suffix is optional"
+    )]
+    pub id: String,
+    #[structopt(
+        name = "KEY=VALUE",
+        required = true,
+        parse(try_from_str = parse_key_value),
+        help = "Sets the keys, the values are written as specified"
+    )]
+    pub keys: Vec<(String, String)>,
+}
+
+#[derive(Debug, StructOpt)]
+pub struct UnsetKeys {
+    #[structopt(
+        name = "ID",
+        help = "Sets the desktop file ID of the entry, the .desktop suffix is optional"
+    )]
+    pub id: String,
+    #[structopt(name = "KEY", required = true, help = "Sets the keys to remove")]
+    pub keys: Vec<String>,
+}
+
+#[derive(Debug, StructOpt)]
+pub struct HideEntry {
+    #[structopt(
+        name = "ID",
+        help = "Sets the desktop file ID of the entry, the .desktop suffix is optional"
+    )]
+    pub id: String,
+    #[structopt(
+        long = "no-display",
+        name = "NoDisplay",
+        help = "Uses NoDisplay instead of Hidden, which hides the entry from menus but keeps it \
+        available, f.e. for opening files",
+        display_order = 1
+    )]
+    pub no_display: bool,
+}
+
+#[derive(Debug, StructOpt)]
+pub struct RemoveEntry {
+    #[structopt(
+        name = "ID",
+        help = "Sets the desktop file ID of the entry, the .desktop suffix is optional"
+    )]
+    pub id: String,
+}
+
+#[derive(Debug, StructOpt)]
+pub struct PruneEntries {
+    #[structopt(
+        short = "y",
+        long = "yes",
+        name = "Yes",
+        help = "Removes the entries without asking",
+        display_order = 1
+    )]
+    pub yes: bool,
+    #[structopt(
+        short = "n",
+        long = "dry-run",
+        name = "DryRun",
+        conflicts_with = "Yes",
+        help = "Only prints the entries which would be removed",
+        display_order = 2
+    )]
+    pub dry_run: bool,
+}
+
+#[derive(Debug, StructOpt)]
+pub struct MimeQuery {
+    #[structopt(name = "MIME_TYPE", help = "Sets the MIME type, f.e. text/html")]
+    pub mime_type: String,
+    #[structopt(
+        short = "a",
+        long = "all",
+        name = "All",
+        help = "Prints all applications associated with the MIME type in the order of their \
+        preference instead of the default one",
+        display_order = 1
+    )]
+    pub all: bool,
+}
+
+#[derive(Debug, StructOpt)]
+pub struct MimeAssociation {
+    #[structopt(name = "MIME_TYPE", help = "Sets the MIME type, f.e. text/html")]
+    pub mime_type: String,
+    #[structopt(
+        name = "ID",
+        help = "Sets the desktop file ID of the application, the .desktop suffix is optional"
+    )]
+    pub id: String,
+}
+
+#[derive(Debug, StructOpt)]
+pub struct OpenTarget {
+    #[structopt(name = "FILE|URL", help = "Sets the file or URL to open")]
+    pub target: String,
+    #[structopt(
+        short = "G",
+        long = "language",
+        name = "Language",
+        help = "Localizes the name passed by the %c field code according to the specified \
+        locale/language instead of $LC_MESSAGES",
+        display_order = 1
+    )]
+    pub lang: Option<String>,
+}
+
+#[derive(Debug, StructOpt)]
+pub struct UpdateCache {
+    #[structopt(
+        name = "DIR",
+        required = true,
+        help = "Sets the applications directories"
+    )]
+    pub dirs: Vec<PathBuf>,
+    #[structopt(
+        long = "check",
+        name = "Check",
+        help = "Only reports the directories whose cache is missing or stale, exiting with an \
+        error if there are any",
+        display_order = 1
+    )]
+    pub check: bool,
+}
+
+#[derive(Debug, StructOpt)]
+pub struct AutostartEntry {
+    #[structopt(
+        name = "ID",
+        help = "Sets the desktop file ID of the entry, the .desktop suffix is optional"
+    )]
+    pub id: String,
+}
+
+#[derive(Debug, StructOpt)]
+pub struct AutostartRun {
+    #[structopt(
+        short = "n",
+        long = "dry-run",
+        name = "DryRun",
+        help = "Only prints the command lines which would be executed",
+        display_order = 1
+    )]
+    pub dry_run: bool,
+    #[structopt(
+        long = "desktop",
+        name = "Desktop",
+        number_of_values = 1,
+        help = "Sets the current desktop environment instead of $XDG_CURRENT_DESKTOP, can be \
+        specified multiple times",
+        display_order = 2
+    )]
+    pub desktops: Vec<String>,
+    #[structopt(
+        short = "G",
+        long = "language",
+        name = "Language",
+        help = "Localizes the name passed by the %c field code according to the specified \
+        locale/language instead of $LC_MESSAGES",
+        display_order = 3
+    )]
+    pub lang: Option<String>,
+}
+
+#[derive(Debug, StructOpt)]
+pub struct MenuOptions {
+    #[structopt(
+        long = "file",
+        name = "MenuFile",
+        help = "Reads the menu from the file instead of \
+        $XDG_CONFIG_DIRS/menus/${XDG_MENU_PREFIX}applications.menu",
+        display_order = 1
+    )]
+    pub file: Option<PathBuf>,
+    #[structopt(
+        short = "f",
+        long = "format",
+        name = "MenuFormat",
+        default_value = "tree",
+        possible_values = MENU_FORMATS,
+        help = "Sets the output format: tree prints the menus and entries indented by their \
+        depth, json prints the whole tree including icons, commands and paths, openbox, jwm, \
+        fluxbox, icewm and twm print menu configurations of these window managers",
+        display_order = 2
+    )]
+    pub format: MenuFormat,
+    #[structopt(
+        long = "desktop",
+        name = "Desktop",
+        number_of_values = 1,
+        help = "Sets the current desktop environment instead of $XDG_CURRENT_DESKTOP, can be \
+        specified multiple times",
+        display_order = 3
+    )]
+    pub desktops: Vec<String>,
+    #[structopt(
+        short = "G",
+        long = "language",
+        name = "Language",
+        help = "Localizes the labels according to the specified locale/language instead of \
+        $LC_MESSAGES",
+        display_order = 4
+    )]
+    pub lang: Option<String>,
 }
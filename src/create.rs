@@ -0,0 +1,294 @@
+use std::error::Error;
+use std::fmt::{self, Display, Formatter};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process;
+
+use super::configuration::NewEntry;
+
+/// The version of the Desktop Entry Specification the generated entries conform to.
+static SPEC_VERSION: &str = "1.5";
+
+/// Parses a KEY=VALUE argument.
+pub fn parse_key_value(argument: &str) -> Result<(String, String), String> {
+    match argument.split_once('=') {
+        Some((key, value)) if !key.trim().is_empty() => {
+            Ok((String::from(key.trim()), String::from(value.trim())))
+        }
+        _ => Err(format!("Expected KEY=VALUE, got {}", argument)),
+    }
+}
+
+/// Derives the name part of a desktop file ID from the Name of an entry, lowercasing it and
+/// replacing every run of other characters than ASCII letters and digits by a dash. The result is
+/// empty if the Name contains no such characters.
+pub fn id_from_name(name: &str) -> String {
+    let mut id = String::with_capacity(name.len());
+    for c in name.chars().flat_map(char::to_lowercase) {
+        if c.is_ascii_alphanumeric() {
+            id.push(c);
+        } else if !id.is_empty() && !id.ends_with('-') {
+            id.push('-');
+        }
+    }
+    id.trim_end_matches('-').to_string()
+}
+
+/// Returns the desktop file ID of an entry, prefixed by the vendor as recommended by the
+/// specification to avoid clashes with entries installed by other vendors. The ID has to follow
+/// the rules of D-Bus well-known names the specification requires: dot separated components of
+/// ASCII letters, digits, dashes and underscores, none of them starting with a digit.
+pub fn desktop_file_id(vendor: Option<&str>, name: &str) -> Result<String, CreateError> {
+    if name.is_empty() {
+        return Err(CreateError::new(String::from(
+            "The desktop file ID cannot be derived from the name, specify it using --id",
+        )));
+    }
+    let id = match vendor {
+        Some(vendor) => format!("{}-{}", vendor, name),
+        None => String::from(name),
+    };
+
+    let valid = id.split('.').all(|component| {
+        component
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+            && component
+                .chars()
+                .next()
+                .is_some_and(|c| !c.is_ascii_digit())
+    });
+    if !valid {
+        return Err(CreateError::new(format!(
+            "Invalid desktop file ID {}.desktop, its dot separated components may contain only \
+            ASCII letters, digits, dashes and underscores and must not start with a digit",
+            id
+        )));
+    }
+    Ok(format!("{}.desktop", id))
+}
+
+/// Escapes a string value as described by the specification.
+pub fn escape_value(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for (index, c) in value.chars().enumerate() {
+        match c {
+            ' ' if index == 0 => escaped.push_str("\\s"),
+            '\n' => escaped.push_str("\\n"),
+            '\t' => escaped.push_str("\\t"),
+            '\r' => escaped.push_str("\\r"),
+            '\\' => escaped.push_str("\\\\"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Joins the values into a list value, splitting values which already contain semicolons.
+fn list_value(values: &[String]) -> String {
+    let mut list = String::new();
+    for value in values.iter().flat_map(|value| value.split(';')) {
+        let value = value.trim();
+        if !value.is_empty() {
+            list.push_str(&escape_value(value));
+            list.push(';');
+        }
+    }
+    list
+}
+
+/// Generates the contents of a new entry. The keys set using --set are written as they were
+/// specified.
+pub fn generate(options: &NewEntry) -> String {
+    let mut keys: Vec<(&str, String)> = vec![
+        ("Type", options.entry_type.clone()),
+        ("Version", String::from(SPEC_VERSION)),
+        ("Name", escape_value(&options.name)),
+    ];
+
+    let strings = [
+        ("GenericName", &options.generic_name),
+        ("Comment", &options.comment),
+        ("Icon", &options.icon),
+        ("TryExec", &options.try_exec),
+        ("Exec", &options.exec),
+        ("Path", &options.path),
+        ("URL", &options.url),
+        ("StartupWMClass", &options.startup_wm_class),
+    ];
+    for (key, value) in &strings {
+        if let Some(value) = value {
+            keys.push((key, escape_value(value)));
+        }
+    }
+
+    let lists = [
+        ("Categories", &options.categories),
+        ("Keywords", &options.keywords),
+        ("MimeType", &options.mime_types),
+    ];
+    for (key, values) in &lists {
+        let value = list_value(values);
+        if !value.is_empty() {
+            keys.push((key, value));
+        }
+    }
+
+    let booleans = [
+        ("Terminal", options.terminal),
+        ("NoDisplay", options.no_display),
+        ("StartupNotify", options.startup_notify),
+    ];
+    for (key, value) in &booleans {
+        if *value {
+            keys.push((key, String::from("true")));
+        }
+    }
+
+    for (key, value) in &options.keys {
+        keys.retain(|(existing, _)| existing != key);
+        keys.push((key, value.clone()));
+    }
+
+    let mut contents = String::from("[Desktop Entry]\n");
+    for (key, value) in keys {
+        contents.push_str(&format!("{}={}\n", key, value));
+    }
+    contents
+}
+
+/// Writes the entry into the directory under its desktop file ID. An existing file is replaced
+/// only if forced. Returns the path of the installed file.
+pub fn install(dir: &Path, id: &str, contents: &str, force: bool) -> io::Result<PathBuf> {
+    if id.contains('/') || !id.ends_with(".desktop") || id == ".desktop" {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("{} is not a valid desktop file ID", id),
+        ));
+    }
+
+    let path = dir.join(id);
+    if path.exists() && !force {
+        return Err(io::Error::new(
+            io::ErrorKind::AlreadyExists,
+            format!("{} already exists", path.display()),
+        ));
+    }
+
+    fs::create_dir_all(dir)?;
+    write_file(&path, contents)?;
+    Ok(path)
+}
+
+/// Replaces the file atomically by writing a temporary file next to it and renaming it.
+pub(crate) fn write_file(path: &Path, contents: &str) -> io::Result<()> {
+    let temp_path = path.with_extension(format!("{}.tmp", process::id()));
+    fs::write(&temp_path, contents)?;
+    fs::rename(&temp_path, path)
+}
+
+#[derive(Debug)]
+pub struct CreateError {
+    message: String,
+}
+
+impl CreateError {
+    fn new(message: String) -> CreateError {
+        CreateError { message }
+    }
+}
+
+impl Error for CreateError {}
+
+impl Display for CreateError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::configuration::{Command, Configuration};
+    use crate::tempdir::TempDir;
+    use crate::validate;
+    use structopt::StructOpt;
+
+    fn new_entry(args: &[&str]) -> NewEntry {
+        let mut full_args = vec!["desktopentries", "new"];
+        full_args.extend(args);
+        match Configuration::from_iter(&full_args).command {
+            Some(Command::New(options)) => *options,
+            _ => panic!("Expected the new subcommand"),
+        }
+    }
+
+    #[test]
+    fn test_generate() {
+        let options = new_entry(&[
+            "--name",
+            "Foo",
+            "--exec",
+            "/opt/foo/bin/foo %U",
+            "--icon",
+            "foo",
+            "--categories",
+            "Development;IDE",
+            "Utility",
+            "--set",
+            "Name[de]=Fuu",
+        ]);
+        let contents = generate(&options);
+
+        assert_eq!(
+            contents,
+            "[Desktop Entry]
+Type=Application
+Version=1.5
+Name=Foo
+Icon=foo
+Exec=/opt/foo/bin/foo %U
+Categories=Development;IDE;Utility;
+Name[de]=Fuu
+"
+        );
+        assert!(validate::validate(&contents).is_empty());
+    }
+
+    #[test]
+    fn test_ids() {
+        assert_eq!(id_from_name("My  Great App 2!"), "my-great-app-2");
+        assert_eq!(id_from_name("Café Ünicode"), "caf-nicode");
+        assert_eq!(id_from_name("!!!"), "");
+        assert_eq!(
+            desktop_file_id(Some("acme"), "foo").unwrap(),
+            "acme-foo.desktop"
+        );
+        assert_eq!(desktop_file_id(None, "foo").unwrap(), "foo.desktop");
+        assert_eq!(
+            desktop_file_id(Some("org.acme"), "foo_bar").unwrap(),
+            "org.acme-foo_bar.desktop"
+        );
+        assert!(desktop_file_id(Some("acme"), "").is_err());
+        assert!(desktop_file_id(None, "2048").is_err());
+        assert!(desktop_file_id(Some("org.7zip"), "foo").is_err());
+        assert!(desktop_file_id(Some("acme"), "foo..bar").is_err());
+        assert!(desktop_file_id(Some("acme"), "foo bar").is_err());
+        assert!(desktop_file_id(Some("acme"), "../foo").is_err());
+        assert_eq!(escape_value(" a\\b\n"), "\\sa\\\\b\\n");
+    }
+
+    #[test]
+    fn test_install() -> io::Result<()> {
+        let dir = TempDir::new("create");
+
+        let path = install(&dir, "acme-foo.desktop", "[Desktop Entry]\n", false)?;
+        assert_eq!(path, dir.join("acme-foo.desktop"));
+        assert!(install(&dir, "acme-foo.desktop", "[Desktop Entry]\n", false).is_err());
+        assert!(install(&dir, "acme-foo.desktop", "[Desktop Entry]\n", true).is_ok());
+        assert!(install(&dir, "../foo.desktop", "[Desktop Entry]\n", true).is_err());
+
+        Ok(())
+    }
+}
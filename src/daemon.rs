@@ -0,0 +1,508 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::Shutdown;
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard, RwLock};
+use std::thread;
+use std::time::{Duration, SystemTime};
+use structopt::StructOpt;
+
+use super::checker::Checker;
+use super::configuration::Configuration;
+use super::discovery::DataDirMode;
+use super::history::History;
+use super::output::MatchedEntry;
+use super::search::{self, Ranking};
+use super::sort;
+use super::watch::Watcher;
+use super::xdg;
+
+static APP_NAME: &str = "desktopentries";
+static SOCKET_NAME: &str = "desktopentries.sock";
+static MAX_CONNECTIONS: usize = 64;
+
+/// A query sent to the server as a single line of JSON. The arguments are the flags and options
+/// of the query, the same ones the command line accepts. The locales are the client's
+/// $LC_MESSAGES, used by the -L flag, and the locale used for collation, so that the answer does
+/// not depend on the environment of the server.
+#[derive(Serialize, Deserialize)]
+pub struct Request {
+    pub args: Vec<String>,
+    #[serde(default)]
+    pub messages_locale: Option<String>,
+    #[serde(default)]
+    pub collation_locale: String,
+}
+
+/// The answer to a query, sent as a single line of JSON. The errors are the parse errors of the
+/// entries known to the server, sent in the first answer on a connection and again only after
+/// the entries were reloaded. The error is set if the query itself was invalid.
+#[derive(Serialize, Deserialize)]
+pub struct Response {
+    pub entries: Vec<MatchedEntry>,
+    pub errors: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl Response {
+    fn failed(error: String) -> Response {
+        Response {
+            entries: Vec::new(),
+            errors: Vec::new(),
+            error: Some(error),
+        }
+    }
+}
+
+/// Returns the path of the socket in $XDG_RUNTIME_DIR.
+pub fn default_socket_path() -> Option<PathBuf> {
+    xdg::runtime_dir().map(|dir| dir.join(SOCKET_NAME))
+}
+
+struct State {
+    entries: Vec<MatchedEntry>,
+    errors: Vec<String>,
+    /// Counts the reloads, so that the errors are sent again when they may have changed.
+    generation: u64,
+}
+
+/// The launch history, read again when the file was modified since it was last read.
+struct HistoryCache {
+    path: Option<PathBuf>,
+    modified: Option<SystemTime>,
+    history: Arc<History>,
+}
+
+impl HistoryCache {
+    fn new(path: Option<PathBuf>) -> HistoryCache {
+        let history = match &path {
+            Some(path) => History::load(path),
+            None => History::load_default(),
+        };
+        let mut cache = HistoryCache {
+            path,
+            modified: None,
+            history: Arc::new(history),
+        };
+        cache.modified = cache.modified();
+        cache
+    }
+
+    fn get(&mut self) -> Arc<History> {
+        if self.modified() != self.modified {
+            self.reload();
+        }
+        Arc::clone(&self.history)
+    }
+
+    fn modified(&self) -> Option<SystemTime> {
+        let path = self.path.as_ref()?;
+        fs::metadata(path)
+            .and_then(|metadata| metadata.modified())
+            .ok()
+    }
+
+    fn reload(&mut self) {
+        if let Some(path) = &self.path {
+            self.modified = self.modified();
+            self.history = Arc::new(History::load(path));
+        }
+    }
+}
+
+/// Keeps the entries returned by the reload function in memory and answers queries sent to the
+/// socket. Every connection is handled by its own thread and may send any number of queries, at
+/// most 64 connections are served at the same time. The directories are watched and the entries
+/// are reloaded when they change. Does not return unless the socket or the watcher fail, the
+/// socket is removed when it does.
+pub fn serve<F>(
+    socket_path: &Path,
+    dirs: &[PathBuf],
+    debounce: Duration,
+    reload: F,
+) -> io::Result<()>
+where
+    F: FnMut() -> (Vec<MatchedEntry>, Vec<String>) + Send,
+{
+    let listener = bind(socket_path)?;
+    let result = Watcher::new(dirs).and_then(|mut watcher| {
+        listen(
+            &listener,
+            socket_path,
+            move || watcher.wait(debounce),
+            reload,
+        )
+    });
+    let _ = fs::remove_file(socket_path);
+    result
+}
+
+/// Answers queries until the wait function, which returns when the entries have to be reloaded,
+/// fails. The failing watcher thread connects to the socket to wake up the accept loop, the
+/// connections still open are shut down, so that their threads end as well.
+fn listen<W, F>(
+    listener: &UnixListener,
+    socket_path: &Path,
+    mut wait: W,
+    mut reload: F,
+) -> io::Result<()>
+where
+    W: FnMut() -> io::Result<()> + Send,
+    F: FnMut() -> (Vec<MatchedEntry>, Vec<String>) + Send,
+{
+    let (entries, errors) = reload();
+    let state = RwLock::new(State {
+        entries,
+        errors,
+        generation: 0,
+    });
+    let state = &state;
+    let history = Mutex::new(HistoryCache::new(History::default_path()));
+    let history = &history;
+    let stopped = AtomicBool::new(false);
+    let stopped = &stopped;
+    let connections = Mutex::new(HashMap::new());
+    let connections = &connections;
+
+    thread::scope(|scope| {
+        let watching = scope.spawn(move || -> io::Result<()> {
+            let result = loop {
+                if let Err(error) = wait() {
+                    break Err(error);
+                }
+                let (entries, errors) = reload();
+                let mut state = state.write().unwrap_or_else(|error| error.into_inner());
+                let generation = state.generation + 1;
+                *state = State {
+                    entries,
+                    errors,
+                    generation,
+                };
+            };
+            stopped.store(true, Ordering::SeqCst);
+            let _ = UnixStream::connect(socket_path);
+            result
+        });
+
+        for (index, stream) in listener.incoming().enumerate() {
+            if stopped.load(Ordering::SeqCst) {
+                break;
+            }
+            match stream.and_then(|stream| Ok((stream.try_clone()?, stream))) {
+                Ok((connection, mut stream)) => {
+                    let mut open_connections = lock(connections);
+                    if open_connections.len() >= MAX_CONNECTIONS {
+                        drop(open_connections);
+                        let response = Response::failed(String::from("Too many connections"));
+                        let _ = write_response(&mut stream, &response);
+                        continue;
+                    }
+                    open_connections.insert(index, connection);
+                    drop(open_connections);
+                    scope.spawn(move || {
+                        if let Err(error) = handle_connection(stream, state, history) {
+                            eprintln!("Could not answer a query: {}", error);
+                        }
+                        lock(connections).remove(&index);
+                    });
+                }
+                Err(error) => eprintln!("Could not accept a connection: {}", error),
+            }
+        }
+
+        for connection in lock(connections).values() {
+            let _ = connection.shutdown(Shutdown::Both);
+        }
+        match watching.join() {
+            Ok(result) => result,
+            Err(_) => Err(io::Error::other("the watcher thread panicked")),
+        }
+    })
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|error| error.into_inner())
+}
+
+/// Binds the socket, replacing a socket left behind by a server which is no longer running.
+fn bind(socket_path: &Path) -> io::Result<UnixListener> {
+    if socket_path.exists() {
+        if UnixStream::connect(socket_path).is_ok() {
+            return Err(io::Error::new(
+                io::ErrorKind::AddrInUse,
+                format!("a server is already listening on {}", socket_path.display()),
+            ));
+        }
+        fs::remove_file(socket_path)?;
+    }
+    if let Some(parent) = socket_path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    UnixListener::bind(socket_path)
+}
+
+fn handle_connection(
+    stream: UnixStream,
+    state: &RwLock<State>,
+    history: &Mutex<HistoryCache>,
+) -> io::Result<()> {
+    let mut writer = stream.try_clone()?;
+    let mut reported_generation = None;
+    for line in BufReader::new(stream).lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let response = match serde_json::from_str::<Request>(&line) {
+            Ok(request) => {
+                let history = lock(history).get();
+                let state = state.read().unwrap_or_else(|error| error.into_inner());
+                let mut response = answer(&request, &state, &history);
+                if response.error.is_none() && reported_generation != Some(state.generation) {
+                    response.errors = state.errors.clone();
+                    reported_generation = Some(state.generation);
+                }
+                response
+            }
+            Err(error) => Response::failed(format!("Invalid request: {}", error)),
+        };
+        write_response(&mut writer, &response)?;
+    }
+    Ok(())
+}
+
+fn write_response(writer: &mut UnixStream, response: &Response) -> io::Result<()> {
+    serde_json::to_writer(&mut *writer, response)?;
+    writer.write_all(b"\n")?;
+    writer.flush()
+}
+
+/// Answers a query using the entries in memory, without the parse errors. Options which only
+/// affect the output are ignored, as the client formats the entries itself. Options selecting the
+/// entries to read are rejected, as the server only knows the entries it was started with.
+fn answer(request: &Request, state: &State, history: &History) -> Response {
+    let args = std::iter::once(APP_NAME).chain(request.args.iter().map(String::as_str));
+    let mut conf = match Configuration::from_iter_safe(args) {
+        Ok(conf) => conf,
+        Err(error) => return Response::failed(error.to_string()),
+    };
+    if !conf.files.is_empty()
+        || !conf.data_dirs.is_empty()
+        || !conf.applications_dirs.is_empty()
+        || conf.data_dir_mode != DataDirMode::Prepend
+        || conf.directory
+        || conf.watch
+        || conf.serve
+        || conf.autostart
+    {
+        return Response::failed(String::from(
+            "Queries cannot specify files, directories, --data-dir-mode, --directory, --watch, \
+            --serve or --autostart",
+        ));
+    }
+
+    // The -L flag reads $LC_MESSAGES, which has to be the client's rather than the server's.
+    if conf.localized && conf.lang.is_none() {
+        conf.lang = request.messages_locale.clone();
+    }
+    conf.localized = false;
+
+    let search_term = conf.search.take();
+    let sort_key = conf.sort.take();
+    let ranking = Ranking {
+        search_term: search_term.as_deref(),
+        sort_key: sort_key.as_ref(),
+        reverse: conf.reverse,
+        limit: conf.limit,
+        collation_locale: &request.collation_locale,
+    };
+    let checker = Checker::new(conf);
+
+    let mut entries: Vec<MatchedEntry> = state
+        .entries
+        .iter()
+        .filter(|matched| checker.check_entry(&matched.entry))
+        .cloned()
+        .collect();
+    search::rank_entries(&mut entries, &ranking, checker.language_strings(), history);
+
+    Response {
+        entries,
+        errors: Vec::new(),
+        error: None,
+    }
+}
+
+/// A connection to a server, which can be used for any number of queries.
+pub struct Client {
+    reader: BufReader<UnixStream>,
+    writer: UnixStream,
+}
+
+impl Client {
+    pub fn connect(socket_path: &Path) -> io::Result<Client> {
+        let writer = UnixStream::connect(socket_path)?;
+        let reader = BufReader::new(writer.try_clone()?);
+        Ok(Client { reader, writer })
+    }
+
+    /// Sends the arguments of a query along with the locales of this process.
+    pub fn query(&mut self, args: &[String]) -> io::Result<Response> {
+        let request = Request {
+            args: args.to_vec(),
+            messages_locale: env::var("LC_MESSAGES").ok(),
+            collation_locale: sort::collation_locale(),
+        };
+        serde_json::to_writer(&mut self.writer, &request)?;
+        self.writer.write_all(b"\n")?;
+        self.writer.flush()?;
+
+        let mut line = String::new();
+        if self.reader.read_line(&mut line)? == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "the server closed the connection",
+            ));
+        }
+        Ok(serde_json::from_str(&line)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tempdir::TempDir;
+
+    #[test]
+    fn test_serve() -> io::Result<()> {
+        let root = TempDir::new("daemon");
+        let applications = root.join("applications");
+        fs::create_dir_all(&applications)?;
+        let socket_path = root.join(SOCKET_NAME);
+
+        let contents = [
+            "[Desktop Entry]\nType=Application\nName=Foo\n",
+            "[Desktop Entry]\nType=Link\nName=Bar\n",
+        ];
+        let entries: Vec<MatchedEntry> = contents
+            .iter()
+            .enumerate()
+            .map(|(index, contents)| MatchedEntry {
+                id: format!("{}.desktop", index),
+                path: applications.join(format!("{}.desktop", index)),
+                contents: String::from(*contents),
+                entry: crate::entry::Entry::new(contents).unwrap(),
+                score: None,
+            })
+            .collect();
+
+        let server_socket_path = socket_path.clone();
+        let dirs = vec![applications];
+        thread::spawn(move || {
+            serve(
+                &server_socket_path,
+                &dirs,
+                Duration::from_millis(10),
+                || {
+                    (
+                        entries.clone(),
+                        vec![String::from("Could not parse 2.desktop")],
+                    )
+                },
+            )
+        });
+
+        let mut client = loop {
+            match Client::connect(&socket_path) {
+                Ok(client) => break client,
+                Err(_) => thread::sleep(Duration::from_millis(10)),
+            }
+        };
+
+        let response = client.query(&[String::from("-a")])?;
+        assert_eq!(response.entries.len(), 1);
+        assert_eq!(response.entries[0].id, "0.desktop");
+        assert_eq!(response.errors.len(), 1);
+
+        let response = client.query(&[String::from("--search"), String::from("bar")])?;
+        assert_eq!(response.entries.len(), 1);
+        assert_eq!(response.entries[0].id, "1.desktop");
+        assert!(response.errors.is_empty());
+
+        let response = client.query(&[String::from("--unknown")])?;
+        assert!(response.error.is_some());
+
+        let response = client.query(&[String::from("-d")])?;
+        assert!(response.error.is_some());
+
+        let response = Client::connect(&socket_path)?.query(&[String::from("-a")])?;
+        assert_eq!(response.errors.len(), 1);
+
+        assert!(bind(&socket_path).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_answer_uses_client_locales() {
+        let contents = "[Desktop Entry]\nType=Application\nName=Fox\nName[de]=Fuchs\n";
+        let state = State {
+            entries: vec![MatchedEntry {
+                id: String::from("fox.desktop"),
+                path: PathBuf::from("/usr/share/applications/fox.desktop"),
+                contents: String::from(contents),
+                entry: crate::entry::Entry::new(contents).unwrap(),
+                score: None,
+            }],
+            errors: Vec::new(),
+            generation: 0,
+        };
+        let history = History::load(Path::new("/nonexistent/desktopentries/history"));
+        let request = |messages_locale: Option<&str>| Request {
+            args: vec![String::from("-g"), String::from("--name=Fuchs")],
+            messages_locale: messages_locale.map(String::from),
+            collation_locale: String::from("C"),
+        };
+
+        let response = answer(&request(Some("de_DE.UTF-8")), &state, &history);
+        assert_eq!(response.entries.len(), 1);
+
+        let response = answer(&request(None), &state, &history);
+        assert!(response.entries.is_empty());
+    }
+
+    #[test]
+    fn test_listen_stops() -> io::Result<()> {
+        let root = TempDir::new("daemon-stop");
+        let socket_path = root.join(SOCKET_NAME);
+        let listener = bind(&socket_path)?;
+
+        let (sender, receiver) = std::sync::mpsc::channel::<()>();
+        let server_socket_path = socket_path.clone();
+        let server = thread::spawn(move || {
+            listen(
+                &listener,
+                &server_socket_path,
+                move || {
+                    let _ = receiver.recv();
+                    Err(io::Error::other("the watcher failed"))
+                },
+                || (Vec::new(), Vec::new()),
+            )
+        });
+
+        let mut client = Client::connect(&socket_path)?;
+        assert!(client.query(&[String::from("-a")])?.entries.is_empty());
+
+        sender.send(()).unwrap();
+        assert!(server.join().unwrap().is_err());
+        assert!(client.query(&[String::from("-a")]).is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,383 @@
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use super::checker::Checker;
+use super::entry::Entry;
+use super::output::MatchedEntry;
+use super::parallel;
+use super::xdg;
+
+/// The path used for entries read from stdin.
+pub static STDIN_PATH: &str = "-";
+
+static DEFAULT_DATA_DIR: &str = "/usr/share/";
+
+/// Specifies how the data directories specified on the command line are combined with the ones
+/// specified by $XDG_DATA_DIRS.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DataDirMode {
+    Prepend,
+    Append,
+    Replace,
+}
+
+impl FromStr for DataDirMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "prepend" => Ok(DataDirMode::Prepend),
+            "append" => Ok(DataDirMode::Append),
+            "replace" => Ok(DataDirMode::Replace),
+            _ => Err(format!("Unknown data directory mode {}", s)),
+        }
+    }
+}
+
+/// The kind of the files collected when scanning a directory.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum EntryKind {
+    /// .desktop files in applications directories, identified by their desktop file IDs.
+    Desktop,
+    /// .directory files in desktop-directories directories, identified by their relative paths
+    /// as menus refer to them.
+    Directory,
+}
+
+/// Returns the applications directories in the order of their precedence. The custom data
+/// directories are followed by the custom applications directories, both are combined with the
+/// directories specified by $XDG_DATA_DIRS according to the mode.
+pub fn application_dirs(
+    data_dirs: &[PathBuf],
+    applications_dirs: &[PathBuf],
+    mode: DataDirMode,
+) -> Vec<PathBuf> {
+    let mut custom_dirs: Vec<PathBuf> = data_dirs
+        .iter()
+        .map(|dir| dir.join("applications"))
+        .collect();
+    custom_dirs.extend(applications_dirs.iter().cloned());
+
+    match mode {
+        DataDirMode::Prepend => {
+            custom_dirs.extend(default_application_dirs());
+            custom_dirs
+        }
+        DataDirMode::Append => {
+            let mut dirs = default_application_dirs();
+            dirs.extend(custom_dirs);
+            dirs
+        }
+        DataDirMode::Replace => custom_dirs,
+    }
+}
+
+/// Returns the applications directories of $XDG_DATA_HOME and $XDG_DATA_DIRS, the user's
+/// directory taking precedence as described by the XDG Base Directory Specification.
+fn default_application_dirs() -> Vec<PathBuf> {
+    let mut data_dirs: Vec<PathBuf> = xdg::data_home().into_iter().collect();
+    match env::var("XDG_DATA_DIRS") {
+        Ok(value) => data_dirs.extend(
+            value
+                .split(':')
+                .filter(|dir| !dir.is_empty())
+                .map(PathBuf::from),
+        ),
+        Err(_) => {
+            eprintln!("$XDG_DATA_DIRS not set, defaulting to {}", DEFAULT_DATA_DIR);
+            data_dirs.push(PathBuf::from(DEFAULT_DATA_DIR));
+        }
+    }
+
+    let mut dirs: Vec<PathBuf> = Vec::with_capacity(data_dirs.len());
+    for dir in data_dirs {
+        let dir = dir.join("applications");
+        if !dirs.contains(&dir) {
+            dirs.push(dir);
+        }
+    }
+    dirs
+}
+
+/// Returns the desktop-directories directories holding the .directory files of the menus, one
+/// for the data directory of each applications directory, in the same order of precedence.
+pub fn directory_dirs(application_dirs: &[PathBuf]) -> Vec<PathBuf> {
+    let mut dirs: Vec<PathBuf> = Vec::with_capacity(application_dirs.len());
+    for dir in application_dirs.iter().filter_map(|dir| dir.parent()) {
+        let dir = dir.join("desktop-directories");
+        if !dirs.contains(&dir) {
+            dirs.push(dir);
+        }
+    }
+    dirs
+}
+
+/// Collects the entries from the applications directories, scanning the directories in parallel.
+/// If multiple entries with the same ID exist, the one in the first directory is used. Within a
+/// directory, the one found first when scanning the directory in alphabetical order is used.
+pub fn get_entries_from_dirs(dirs: &[PathBuf], jobs: usize) -> HashMap<String, PathBuf> {
+    collect_entries(dirs, EntryKind::Desktop, jobs)
+}
+
+/// Collects the .directory files from the desktop-directories directories the same way as
+/// get_entries_from_dirs collects the entries.
+pub fn get_directory_entries(dirs: &[PathBuf], jobs: usize) -> HashMap<String, PathBuf> {
+    collect_entries(dirs, EntryKind::Directory, jobs)
+}
+
+fn collect_entries(dirs: &[PathBuf], kind: EntryKind, jobs: usize) -> HashMap<String, PathBuf> {
+    let found_in_dirs = parallel::map(dirs, jobs, |dir| {
+        let mut entries = HashMap::new();
+        scan_path(&base_path(dir), dir, kind, &mut entries, &mut Vec::new());
+        entries
+    });
+
+    let mut entries = HashMap::new();
+    for found in found_in_dirs {
+        for (id, path) in found {
+            entries.entry(id).or_insert(path);
+        }
+    }
+    entries
+}
+
+/// Reads and parses the entries using a pool of the specified number of threads, keeping only
+/// the entries passing the checker. If the extra keys are specified, the entries are parsed
+/// lazily, storing only these keys and the keys read by the checker. The matched entries keep the
+/// order of the sources, errors are returned as messages in the same order.
+pub fn load_entries(
+    sources: Vec<(String, PathBuf)>,
+    checker: &Checker,
+    extra_keys: Option<&[&str]>,
+    jobs: usize,
+) -> (Vec<MatchedEntry>, Vec<String>) {
+    let results = parallel::map(&sources, jobs, |(_, entry_path)| {
+        let contents = match read_entry(entry_path) {
+            Ok(contents) => contents,
+            Err(_) => return Err(format!("Could not read file {}", entry_path.display())),
+        };
+        let entry = match extra_keys {
+            Some(_) => checker.parse_entry(&contents, extra_keys),
+            None => Entry::new(&contents)
+                .map(|entry| Some(entry).filter(|entry| checker.check_entry(entry))),
+        };
+        match entry {
+            Ok(entry) => Ok(entry.map(|entry| (contents, entry))),
+            Err(error) => Err(format!("{} in {}", error, entry_path.display())),
+        }
+    });
+
+    let mut matches = Vec::new();
+    let mut errors = Vec::new();
+    for ((id, path), result) in sources.into_iter().zip(results) {
+        match result {
+            Ok(Some((contents, entry))) => matches.push(MatchedEntry {
+                id,
+                path,
+                contents,
+                entry,
+                score: None,
+            }),
+            Ok(None) => {}
+            Err(error) => errors.push(error),
+        }
+    }
+    (matches, errors)
+}
+
+/// Collects the entries from the files and directories specified on the command line. Files are
+/// identified by their file names, directories are scanned the same way as the data directories
+/// and take precedence in the specified order.
+pub fn get_entries_from_arguments(paths: &[PathBuf]) -> Vec<(String, PathBuf)> {
+    let mut sources = Vec::new();
+    let mut found_in_dirs = HashMap::new();
+
+    for path in paths {
+        if path.as_os_str() == STDIN_PATH {
+            sources.push((String::from(STDIN_PATH), path.clone()));
+        } else if path.is_dir() {
+            get_entries_from_path(&base_path(path), path, &mut found_in_dirs);
+        } else {
+            let id = match path.file_name() {
+                Some(file_name) => file_name.to_string_lossy().into_owned(),
+                None => path.display().to_string(),
+            };
+            sources.push((id, path.clone()));
+        }
+    }
+
+    sources.extend(found_in_dirs);
+    sources
+}
+
+pub(crate) fn base_path(dir: &Path) -> String {
+    let mut base_path = dir.display().to_string();
+    if !base_path.ends_with('/') {
+        base_path.push('/');
+    }
+    base_path
+}
+
+/// Reads the contents of an entry, reading from stdin if the path is -.
+pub fn read_entry(path: &Path) -> io::Result<String> {
+    if path.as_os_str() == STDIN_PATH {
+        let mut contents = String::new();
+        io::stdin().read_to_string(&mut contents)?;
+        Ok(contents)
+    } else {
+        fs::read_to_string(path)
+    }
+}
+
+pub fn get_entries_from_path(base_path: &str, path: &Path, entries: &mut HashMap<String, PathBuf>) {
+    scan_path(
+        base_path,
+        path,
+        EntryKind::Desktop,
+        entries,
+        &mut Vec::new(),
+    );
+}
+
+/// Collects the files of the specified kind the same way as get_entries_from_path, additionally
+/// collecting the paths of the scanned directory and all of its subdirectories.
+pub fn scan_path(
+    base_path: &str,
+    path: &Path,
+    kind: EntryKind,
+    entries: &mut HashMap<String, PathBuf>,
+    dirs: &mut Vec<PathBuf>,
+) {
+    dirs.push(path.to_path_buf());
+
+    let dir_iterator = fs::read_dir(path);
+    match dir_iterator {
+        Ok(dir_iterator) => {
+            let mut files = Vec::new();
+            for file in dir_iterator {
+                match file {
+                    Ok(file) => files.push(file),
+                    Err(_) => eprintln!(
+                        "There was an error while iterating over folder contents \
+                    of {}",
+                        path.display()
+                    ),
+                }
+            }
+            files.sort_by_key(|file| file.file_name());
+
+            for file in files {
+                let file_type = file.file_type();
+                match file_type {
+                    Ok(file_type) => {
+                        if file_type.is_dir() {
+                            scan_path(base_path, file.path().as_path(), kind, entries, dirs);
+                        } else if file_type.is_file() {
+                            let file_name = file.path().display().to_string();
+                            let key = match kind {
+                                EntryKind::Desktop if file_name.ends_with(".desktop") => {
+                                    file_name.replace(base_path, "").replace('/', "-")
+                                }
+                                EntryKind::Directory if file_name.ends_with(".directory") => {
+                                    file_name.replace(base_path, "")
+                                }
+                                _ => continue,
+                            };
+                            entries.entry(key).or_insert(file.path());
+                        }
+                    }
+                    Err(_) => eprintln!("Could not get file type of {}", file.path().display()),
+                }
+            }
+        }
+        // Data directories without an applications directory are common
+        Err(error) if error.kind() == io::ErrorKind::NotFound => {}
+        Err(_) => eprintln!("Could not read path {}", path.display()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tempdir::TempDir;
+
+    fn create_file(path: &Path, contents: &str) {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, contents).unwrap();
+    }
+
+    #[test]
+    fn test_get_entries_from_dirs() {
+        let root = TempDir::new("discovery");
+        create_file(&root.join("first/applications/foo.desktop"), "");
+        create_file(&root.join("second/applications/foo.desktop"), "");
+        create_file(&root.join("second/applications/vendor/bar.desktop"), "");
+        create_file(&root.join("custom/baz.desktop"), "");
+        create_file(&root.join("custom/ignored.txt"), "");
+        create_file(&root.join("second/applications/foo.directory"), "");
+
+        let dirs = application_dirs(
+            &[root.join("first"), root.join("second")],
+            &[root.join("custom")],
+            DataDirMode::Replace,
+        );
+        let entries = get_entries_from_dirs(&dirs, 2);
+
+        assert_eq!(entries.len(), 3);
+        assert_eq!(
+            entries["foo.desktop"],
+            root.join("first/applications/foo.desktop")
+        );
+        assert_eq!(
+            entries["vendor-bar.desktop"],
+            root.join("second/applications/vendor/bar.desktop")
+        );
+        assert_eq!(entries["baz.desktop"], root.join("custom/baz.desktop"));
+        assert!(!entries.contains_key("foo.directory"));
+    }
+
+    #[test]
+    fn test_get_directory_entries() {
+        let root = TempDir::new("directories");
+        create_file(&root.join("first/desktop-directories/Games.directory"), "");
+        create_file(&root.join("second/desktop-directories/Games.directory"), "");
+        create_file(
+            &root.join("second/desktop-directories/Office.directory"),
+            "",
+        );
+        create_file(
+            &root.join("second/desktop-directories/vendor/Tools.directory"),
+            "",
+        );
+        create_file(&root.join("second/applications/foo.desktop"), "");
+
+        let dirs = directory_dirs(&application_dirs(
+            &[root.join("first"), root.join("second")],
+            &[],
+            DataDirMode::Replace,
+        ));
+        assert_eq!(
+            dirs,
+            vec![
+                root.join("first/desktop-directories"),
+                root.join("second/desktop-directories")
+            ]
+        );
+        let entries = get_directory_entries(&dirs, 2);
+
+        assert_eq!(entries.len(), 3);
+        assert_eq!(
+            entries["Games.directory"],
+            root.join("first/desktop-directories/Games.directory")
+        );
+        assert_eq!(
+            entries["vendor/Tools.directory"],
+            root.join("second/desktop-directories/vendor/Tools.directory")
+        );
+        assert!(entries.contains_key("Office.directory"));
+    }
+}
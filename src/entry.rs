@@ -1,45 +1,80 @@
 use core::fmt;
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::error::Error;
 use std::fmt::{Display, Formatter};
 
+#[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct Entry {
     entries: HashMap<String, String>,
 }
 
 impl Entry {
-    pub fn new(contents: &String) -> Result<Entry, ParseEntryError> {
+    pub fn new(contents: &str) -> Result<Entry, ParseEntryError> {
+        let entry = Entry::parse_filtered(contents, |_| true, |_, _| true)?;
+        Ok(entry.unwrap_or(Entry {
+            entries: HashMap::new(),
+        }))
+    }
+
+    /// Parses the entry, storing only the keys accepted by the filter. After a key is stored, the
+    /// callback decides whether the parsing continues. Returns None if the parsing was stopped,
+    /// in which case the rest of the entry is not validated.
+    pub fn parse_filtered<F, C>(
+        contents: &str,
+        mut filter: F,
+        mut callback: C,
+    ) -> Result<Option<Entry>, ParseEntryError>
+    where
+        F: FnMut(&str) -> bool,
+        C: FnMut(&str, &HashMap<String, String>) -> bool,
+    {
         let mut entries = HashMap::new();
 
         for (index, line) in contents.lines().enumerate() {
-            if line == "[Desktop Entry]" {
-                continue;
-            } else if line.starts_with('#') {
-                continue;
-            } else if line.trim() == "" {
+            if line == "[Desktop Entry]" || line.starts_with('#') || line.trim() == "" {
                 continue;
             } else if line.starts_with('[') {
                 break;
             } else {
-                let split: Vec<&str> = line.splitn(2, '=').collect();
-                if split.len() < 2 {
-                    return Err(ParseEntryError::new(format!(
-                        "Could not extract value from line {}: {}",
-                        index, line
-                    )));
+                let (key, value) = match line.split_once('=') {
+                    Some(split) => split,
+                    None => {
+                        return Err(ParseEntryError::new(format!(
+                            "Could not extract value from line {}: {}",
+                            index, line
+                        )))
+                    }
+                };
+                let key = key.trim();
+                if filter(key) {
+                    entries.insert(String::from(key), String::from(value.trim()));
+                    if !callback(key, &entries) {
+                        return Ok(None);
+                    }
                 }
-                let key = split[0].trim();
-                let value = split[1].trim();
-                entries.insert(String::from(key), String::from(value));
             }
         }
-        Ok(Entry { entries })
+        Ok(Some(Entry { entries }))
     }
 
     pub fn get_entries(&self) -> &HashMap<String, String> {
         &self.entries
     }
 
+    pub fn get(&self, key: &str) -> Option<&String> {
+        self.entries.get(key)
+    }
+
+    pub fn get_localized(&self, key: &str, language_strings: &[String]) -> Option<&String> {
+        for string in language_strings {
+            if let Some(value) = self.entries.get(&format!("{}{}", key, string)) {
+                return Some(value);
+            }
+        }
+        self.entries.get(key)
+    }
+
     #[cfg(test)]
     pub fn from_entries(entries: HashMap<String, String>) -> Entry {
         Entry { entries }
@@ -69,7 +104,6 @@ impl Display for ParseEntryError {
 mod test {
     use super::*;
     use std::error::Error;
-    use std::io::{Seek, SeekFrom, Write};
 
     #[test]
     fn test_simple_file() -> Result<(), Box<dyn Error>> {
@@ -110,6 +144,59 @@ Exec=/usr/bin/foo -bar",
         Ok(())
     }
 
+    #[test]
+    fn test_parse_filtered() -> Result<(), Box<dyn Error>> {
+        let contents = String::from(
+            "[Desktop Entry]
+Type=Application
+Name=Foo
+Name[de]=Fuu
+Exec=foo",
+        );
+
+        let entry =
+            Entry::parse_filtered(&contents, |key| key.starts_with("Name"), |_, _| true)?.unwrap();
+        assert_eq!(entry.get_entries().len(), 2);
+        assert_eq!(entry.get("Name[de]"), Some(&String::from("Fuu")));
+
+        let mut seen = Vec::new();
+        let entry = Entry::parse_filtered(
+            &contents,
+            |_| true,
+            |key, _| {
+                seen.push(String::from(key));
+                key != "Name"
+            },
+        )?;
+        assert!(entry.is_none());
+        assert_eq!(seen, vec!["Type", "Name"]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_get_localized() -> Result<(), Box<dyn Error>> {
+        let contents = String::from(
+            "[Desktop Entry]
+Name=Foo
+Name[de]=Fuu
+Comment=Bar",
+        );
+
+        let entry = Entry::new(&contents)?;
+        let language_strings = vec![String::from("[de_DE]"), String::from("[de]")];
+
+        assert_eq!(
+            entry.get_localized("Name", &language_strings),
+            Some(&String::from("Fuu"))
+        );
+        assert_eq!(
+            entry.get_localized("Comment", &language_strings),
+            Some(&String::from("Bar"))
+        );
+        assert_eq!(entry.get_localized("GenericName", &language_strings), None);
+        Ok(())
+    }
+
     #[test]
     fn test_invalid_file() -> Result<(), Box<dyn Error>> {
         let contents = String::from(
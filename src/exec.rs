@@ -0,0 +1,454 @@
+use core::fmt;
+use std::env;
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+use super::entry::Entry;
+
+static DEFAULT_TERMINAL: &str = "xterm";
+
+/// Splits the value of an Exec key into arguments as described by the Desktop Entry
+/// Specification. The value is unescaped first, then split on whitespace while respecting
+/// double-quoted arguments.
+pub fn split_exec(exec: &str) -> Result<Vec<String>, ExecError> {
+    let exec = unescape_string(exec);
+    let mut arguments = Vec::new();
+    let mut current = String::new();
+    let mut in_argument = false;
+    let mut quoted = false;
+    let mut chars = exec.chars();
+
+    while let Some(c) = chars.next() {
+        if quoted {
+            match c {
+                '"' => quoted = false,
+                '\\' => match chars.next() {
+                    Some(escaped @ '"') | Some(escaped @ '`') | Some(escaped @ '$')
+                    | Some(escaped @ '\\') => current.push(escaped),
+                    Some(other) => {
+                        current.push('\\');
+                        current.push(other);
+                    }
+                    None => current.push('\\'),
+                },
+                _ => current.push(c),
+            }
+        } else if c == '"' {
+            quoted = true;
+            in_argument = true;
+        } else if c.is_whitespace() {
+            if in_argument {
+                arguments.push(current);
+                current = String::new();
+                in_argument = false;
+            }
+        } else {
+            current.push(c);
+            in_argument = true;
+        }
+    }
+
+    if quoted {
+        return Err(ExecError::new(format!(
+            "Unterminated quoted argument in Exec value: {}",
+            exec
+        )));
+    }
+    if in_argument {
+        arguments.push(current);
+    }
+    if arguments.is_empty() {
+        return Err(ExecError::new(String::from("Exec value is empty")));
+    }
+
+    Ok(arguments)
+}
+
+/// Expands the field codes of the entry's Exec key. Returns one command line for every process
+/// which should be started, since the single file codes (%f, %u) require a separate process for
+/// each file. Files are appended to the command line if the Exec key has no file field code.
+pub fn expand_exec(
+    entry: &Entry,
+    entry_path: &Path,
+    files: &[String],
+    language_strings: &[String],
+) -> Result<Vec<Vec<String>>, ExecError> {
+    let exec = match entry.get("Exec") {
+        Some(exec) => exec,
+        None => return Err(ExecError::new(String::from("Entry has no Exec key"))),
+    };
+    let arguments = split_exec(exec)?;
+
+    let single_code = arguments
+        .iter()
+        .any(|argument| argument.contains("%f") || argument.contains("%u"));
+    let multi_code = arguments
+        .iter()
+        .any(|argument| argument.contains("%F") || argument.contains("%U"));
+
+    let mut commands = Vec::new();
+    if files.len() > 1 && single_code && !multi_code {
+        for file in files {
+            let command = expand_arguments(
+                &arguments,
+                entry,
+                entry_path,
+                std::slice::from_ref(file),
+                language_strings,
+            );
+            commands.push(command);
+        }
+    } else {
+        let mut command = expand_arguments(&arguments, entry, entry_path, files, language_strings);
+        if !single_code && !multi_code {
+            command.extend(files.iter().cloned());
+        }
+        commands.push(command);
+    }
+
+    if entry.get("Terminal").is_some_and(|value| value == "true") {
+        let terminal = terminal_command();
+        for command in &mut commands {
+            let mut wrapped = terminal.clone();
+            wrapped.append(command);
+            *command = wrapped;
+        }
+    }
+
+    Ok(commands)
+}
+
+/// Starts the processes described by the entry's Exec key without waiting for them to finish.
+/// Entries with DBusActivatable=true are launched using the Exec key as well, D-Bus activation is
+/// not supported.
+pub fn launch(
+    entry: &Entry,
+    entry_path: &Path,
+    files: &[String],
+    language_strings: &[String],
+) -> Result<(), ExecError> {
+    if let Some(entry_type) = entry.get("Type") {
+        if entry_type != "Application" {
+            return Err(ExecError::new(format!(
+                "Entries of Type {} cannot be launched",
+                entry_type
+            )));
+        }
+    }
+
+    for command in expand_exec(entry, entry_path, files, language_strings)? {
+        let mut process = Command::new(&command[0]);
+        process
+            .args(&command[1..])
+            .stdin(Stdio::null())
+            .stdout(Stdio::null());
+        if let Some(working_dir) = entry.get("Path") {
+            if !working_dir.is_empty() {
+                process.current_dir(working_dir);
+            }
+        }
+
+        if let Err(error) = process.spawn() {
+            return Err(ExecError::new(format!(
+                "Could not run {}: {}",
+                command[0], error
+            )));
+        }
+    }
+    Ok(())
+}
+
+fn expand_arguments(
+    arguments: &[String],
+    entry: &Entry,
+    entry_path: &Path,
+    files: &[String],
+    language_strings: &[String],
+) -> Vec<String> {
+    let mut expanded = Vec::with_capacity(arguments.len());
+
+    for argument in arguments {
+        match argument.as_str() {
+            "%F" | "%U" => expanded.extend(files.iter().cloned()),
+            "%i" => {
+                if let Some(icon) = entry.get("Icon") {
+                    expanded.push(String::from("--icon"));
+                    expanded.push(icon.clone());
+                }
+            }
+            "%f" | "%u" | "%d" | "%D" | "%n" | "%N" | "%v" | "%m" => {
+                if argument == "%f" || argument == "%u" {
+                    if let Some(file) = files.first() {
+                        expanded.push(file.clone());
+                    }
+                }
+            }
+            _ => expanded.push(expand_argument(
+                argument,
+                entry,
+                entry_path,
+                files,
+                language_strings,
+            )),
+        }
+    }
+
+    expanded
+}
+
+fn expand_argument(
+    argument: &str,
+    entry: &Entry,
+    entry_path: &Path,
+    files: &[String],
+    language_strings: &[String],
+) -> String {
+    let mut expanded = String::with_capacity(argument.len());
+    let mut chars = argument.chars();
+
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            expanded.push(c);
+            continue;
+        }
+
+        match chars.next() {
+            Some('%') => expanded.push('%'),
+            Some('f') | Some('u') => {
+                if let Some(file) = files.first() {
+                    expanded.push_str(file);
+                }
+            }
+            Some('F') | Some('U') => expanded.push_str(&files.join(" ")),
+            Some('i') => {
+                if let Some(icon) = entry.get("Icon") {
+                    expanded.push_str(icon);
+                }
+            }
+            Some('c') => {
+                if let Some(name) = entry.get_localized("Name", language_strings) {
+                    expanded.push_str(name);
+                }
+            }
+            Some('k') => expanded.push_str(&entry_path.display().to_string()),
+            _ => {}
+        }
+    }
+
+    expanded
+}
+
+/// Joins the arguments into a command line for a POSIX shell, quoting them where needed.
+pub fn command_line(arguments: &[String]) -> String {
+    let quoted: Vec<String> = arguments.iter().map(|argument| quote(argument)).collect();
+    quoted.join(" ")
+}
+
+/// Quotes the argument for a POSIX shell unless it consists of safe characters only.
+pub fn quote(argument: &str) -> String {
+    let safe = !argument.is_empty()
+        && argument
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "_@%+=:,./-".contains(c));
+    if safe {
+        String::from(argument)
+    } else {
+        format!("'{}'", argument.replace('\'', "'\\''"))
+    }
+}
+
+/// Returns the program started by an Exec value, skipping env invocations.
+pub fn program(exec: &str) -> Option<String> {
+    let arguments = split_exec(exec).ok()?;
+    let mut arguments = arguments.into_iter().peekable();
+
+    if arguments.peek().map(|argument| argument.as_str()) == Some("env") {
+        arguments.next();
+        while let Some(argument) = arguments.peek() {
+            if argument.starts_with('-') || argument.contains('=') {
+                arguments.next();
+            } else {
+                break;
+            }
+        }
+    }
+
+    arguments.next()
+}
+
+/// Returns the path of the executable, which is looked up in $PATH unless it is specified as a
+/// path, the same way as the TryExec key is resolved.
+pub fn find_executable(program: &str) -> Option<PathBuf> {
+    if program.contains('/') {
+        let path = PathBuf::from(program);
+        return if is_executable(&path) {
+            Some(path)
+        } else {
+            None
+        };
+    }
+
+    let paths = env::var_os("PATH")?;
+    env::split_paths(&paths)
+        .map(|dir| dir.join(program))
+        .find(|path| is_executable(path))
+}
+
+/// Returns the program of the entry which cannot be found, checking TryExec first, then the
+/// program started by Exec.
+pub fn missing_program(entry: &Entry) -> Option<String> {
+    if let Some(try_exec) = entry.get("TryExec") {
+        if find_executable(try_exec).is_none() {
+            return Some(try_exec.clone());
+        }
+    }
+    let program = program(entry.get("Exec")?)?;
+    if find_executable(&program).is_none() {
+        Some(program)
+    } else {
+        None
+    }
+}
+
+fn is_executable(path: &Path) -> bool {
+    path.metadata()
+        .is_ok_and(|metadata| metadata.is_file() && metadata.permissions().mode() & 0o111 != 0)
+}
+
+fn terminal_command() -> Vec<String> {
+    let terminal = env::var("TERMINAL").unwrap_or_else(|_| String::from(DEFAULT_TERMINAL));
+    let mut command: Vec<String> = terminal.split_whitespace().map(String::from).collect();
+    if command.is_empty() {
+        command.push(String::from(DEFAULT_TERMINAL));
+    }
+    command.push(String::from("-e"));
+    command
+}
+
+fn unescape_string(value: &str) -> String {
+    let mut unescaped = String::with_capacity(value.len());
+    let mut chars = value.chars();
+
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            unescaped.push(c);
+            continue;
+        }
+
+        match chars.next() {
+            Some('s') => unescaped.push(' '),
+            Some('n') => unescaped.push('\n'),
+            Some('t') => unescaped.push('\t'),
+            Some('r') => unescaped.push('\r'),
+            Some('\\') => unescaped.push('\\'),
+            Some(other) => {
+                unescaped.push('\\');
+                unescaped.push(other);
+            }
+            None => unescaped.push('\\'),
+        }
+    }
+
+    unescaped
+}
+
+#[derive(Debug)]
+pub struct ExecError {
+    message: String,
+}
+
+impl ExecError {
+    fn new(message: String) -> ExecError {
+        ExecError { message }
+    }
+}
+
+impl Error for ExecError {}
+
+impl Display for ExecError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use std::path::PathBuf;
+
+    fn create_entry(exec: &str) -> Entry {
+        let mut entries = HashMap::new();
+        entries.insert(String::from("Type"), String::from("Application"));
+        entries.insert(String::from("Name"), String::from("Foo"));
+        entries.insert(String::from("Icon"), String::from("foo-icon"));
+        entries.insert(String::from("Exec"), String::from(exec));
+        Entry::from_entries(entries)
+    }
+
+    #[test]
+    fn test_split_exec() -> Result<(), Box<dyn Error>> {
+        let arguments = split_exec(r#"foo --bar "quoted \"argument\"" "a\\\\b"  last"#)?;
+
+        assert_eq!(
+            arguments,
+            vec!["foo", "--bar", "quoted \"argument\"", "a\\b", "last"]
+        );
+        assert!(split_exec("foo \"unterminated").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_missing_program() {
+        assert_eq!(missing_program(&create_entry("sh -c true")), None);
+        assert_eq!(
+            missing_program(&create_entry("env FOO=1 desktopentries-missing %U")),
+            Some(String::from("desktopentries-missing"))
+        );
+        assert_eq!(
+            missing_program(&create_entry("/nonexistent/foo")),
+            Some(String::from("/nonexistent/foo"))
+        );
+    }
+
+    #[test]
+    fn test_expand_codes() -> Result<(), Box<dyn Error>> {
+        let entry = create_entry("foo %i --name=%c %k %% %U");
+        let path = PathBuf::from("/usr/share/applications/foo.desktop");
+        let files = vec![String::from("a"), String::from("b")];
+
+        let commands = expand_exec(&entry, &path, &files, &[])?;
+
+        assert_eq!(
+            commands,
+            vec![vec![
+                "foo",
+                "--icon",
+                "foo-icon",
+                "--name=Foo",
+                "/usr/share/applications/foo.desktop",
+                "%",
+                "a",
+                "b"
+            ]]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_expand_single_file_codes() -> Result<(), Box<dyn Error>> {
+        let entry = create_entry("foo %f");
+        let path = PathBuf::from("foo.desktop");
+        let files = vec![String::from("a"), String::from("b")];
+
+        assert_eq!(
+            expand_exec(&entry, &path, &files, &[])?,
+            vec![vec!["foo", "a"], vec!["foo", "b"]]
+        );
+        assert_eq!(expand_exec(&entry, &path, &[], &[])?, vec![vec!["foo"]]);
+        Ok(())
+    }
+}
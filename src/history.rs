@@ -0,0 +1,178 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use super::xdg;
+
+static DAY: u64 = 24 * 60 * 60;
+static MAX_SEARCH_BONUS: u64 = 2000;
+
+struct Record {
+    count: u64,
+    last_used: u64,
+}
+
+/// Keeps track of how often and how recently the entries were launched. The history is stored as
+/// tab separated lines containing the ID, launch count and time of the last launch.
+pub struct History {
+    path: Option<PathBuf>,
+    records: HashMap<String, Record>,
+}
+
+impl History {
+    /// Returns the path of the history in $XDG_STATE_HOME.
+    pub fn default_path() -> Option<PathBuf> {
+        xdg::state_home().map(|mut path| {
+            path.push("desktopentries");
+            path.push("history");
+            path
+        })
+    }
+
+    pub fn load_default() -> History {
+        match History::default_path() {
+            Some(path) => History::load(&path),
+            None => History {
+                path: None,
+                records: HashMap::new(),
+            },
+        }
+    }
+
+    pub fn load(path: &Path) -> History {
+        let mut records = HashMap::new();
+
+        if let Ok(contents) = fs::read_to_string(path) {
+            for line in contents.lines() {
+                let split: Vec<&str> = line.split('\t').collect();
+                if split.len() != 3 {
+                    continue;
+                }
+                if let (Ok(count), Ok(last_used)) = (split[1].parse(), split[2].parse()) {
+                    records.insert(String::from(split[0]), Record { count, last_used });
+                }
+            }
+        }
+
+        History {
+            path: Some(path.to_path_buf()),
+            records,
+        }
+    }
+
+    pub fn record(&mut self, id: &str, now: u64) {
+        let record = self.records.entry(String::from(id)).or_insert(Record {
+            count: 0,
+            last_used: now,
+        });
+        record.count += 1;
+        record.last_used = now;
+    }
+
+    pub fn save(&self) -> io::Result<()> {
+        let path = match &self.path {
+            Some(path) => path,
+            None => {
+                return Err(io::Error::new(
+                    io::ErrorKind::NotFound,
+                    "could not determine the state directory",
+                ))
+            }
+        };
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        let mut ids: Vec<&String> = self.records.keys().collect();
+        ids.sort();
+
+        let temp_path = path.with_extension(format!("{}.tmp", process::id()));
+        let mut file = fs::File::create(&temp_path)?;
+        for id in ids {
+            let record = &self.records[id];
+            writeln!(file, "{}\t{}\t{}", id, record.count, record.last_used)?;
+        }
+        file.sync_all()?;
+        fs::rename(&temp_path, path)
+    }
+
+    /// Combines the launch count with the time since the last launch, so that entries used often
+    /// and recently are ranked higher than entries which used to be popular a long time ago.
+    pub fn frecency(&self, id: &str, now: u64) -> u64 {
+        match self.records.get(id) {
+            Some(record) => {
+                let age = now.saturating_sub(record.last_used);
+                let weight = if age < 4 * DAY {
+                    100
+                } else if age < 14 * DAY {
+                    70
+                } else if age < 31 * DAY {
+                    50
+                } else if age < 90 * DAY {
+                    30
+                } else {
+                    10
+                };
+                record.count * weight
+            }
+            None => 0,
+        }
+    }
+
+    /// Returns the bonus added to the fuzzy search score of the entry.
+    pub fn search_bonus(&self, id: &str, now: u64) -> i64 {
+        (self.frecency(id, now).min(MAX_SEARCH_BONUS) / 10) as i64
+    }
+}
+
+pub fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tempdir::TempDir;
+
+    #[test]
+    fn test_frecency() {
+        let mut history = History {
+            path: None,
+            records: HashMap::new(),
+        };
+        let now = 100 * DAY;
+
+        history.record("old.desktop", 0);
+        history.record("old.desktop", 0);
+        history.record("old.desktop", 0);
+        history.record("recent.desktop", now - DAY);
+
+        assert_eq!(history.frecency("old.desktop", now), 30);
+        assert_eq!(history.frecency("recent.desktop", now), 100);
+        assert_eq!(history.frecency("unknown.desktop", now), 0);
+        assert_eq!(history.search_bonus("recent.desktop", now), 10);
+    }
+
+    #[test]
+    fn test_save_and_load() -> io::Result<()> {
+        let dir = TempDir::new("history");
+        let path = dir.join("history");
+
+        let mut history = History::load(&path);
+        history.record("foo.desktop", 10);
+        history.record("foo.desktop", 20);
+        history.save()?;
+
+        let loaded = History::load(&path);
+        let record = &loaded.records["foo.desktop"];
+        assert_eq!((record.count, record.last_used), (2, 20));
+
+        Ok(())
+    }
+}
@@ -0,0 +1,183 @@
+use std::fmt::{self, Display, Formatter};
+
+/// The group of a desktop entry holding its keys.
+pub static DESKTOP_ENTRY_GROUP: &str = "Desktop Entry";
+
+/// A key file which is edited line by line, so that comments, blank lines, other groups and the
+/// order of the keys are preserved.
+pub struct KeyFile {
+    lines: Vec<String>,
+}
+
+impl KeyFile {
+    pub fn parse(contents: &str) -> KeyFile {
+        KeyFile {
+            lines: contents.lines().map(String::from).collect(),
+        }
+    }
+
+    /// Returns the value of the key in the group, the first one if the key is duplicated.
+    pub fn get(&self, group: &str, key: &str) -> Option<&str> {
+        let (start, end) = self.group_range(group)?;
+        self.lines[start..end]
+            .iter()
+            .find_map(|line| match split_line(line) {
+                Some((line_key, value)) if line_key == key => Some(value),
+                _ => None,
+            })
+    }
+
+    /// Sets the key in the group. An existing key keeps its position and its duplicates are
+    /// removed, a new key is added after the last key of the group. A missing group is added at
+    /// the end.
+    pub fn set(&mut self, group: &str, key: &str, value: &str) {
+        let line = format!("{}={}", key, value);
+        let (start, end) = match self.group_range(group) {
+            Some(range) => range,
+            None => {
+                if self
+                    .lines
+                    .last()
+                    .is_some_and(|last| !last.trim().is_empty())
+                {
+                    self.lines.push(String::new());
+                }
+                self.lines.push(format!("[{}]", group));
+                self.lines.push(line);
+                return;
+            }
+        };
+
+        let positions = self.key_positions(start, end, key);
+        match positions.first() {
+            Some(&first) => {
+                self.lines[first] = line;
+                for &position in positions[1..].iter().rev() {
+                    self.lines.remove(position);
+                }
+            }
+            None => {
+                let mut insert_at = end;
+                while insert_at > start && self.lines[insert_at - 1].trim().is_empty() {
+                    insert_at -= 1;
+                }
+                self.lines.insert(insert_at, line);
+            }
+        }
+    }
+
+    /// Removes all occurrences of the key from the group. Returns whether the key was present.
+    pub fn unset(&mut self, group: &str, key: &str) -> bool {
+        let (start, end) = match self.group_range(group) {
+            Some(range) => range,
+            None => return false,
+        };
+        let positions = self.key_positions(start, end, key);
+        for &position in positions.iter().rev() {
+            self.lines.remove(position);
+        }
+        !positions.is_empty()
+    }
+
+    /// Returns the range of the lines following the header of the group up to the next group.
+    fn group_range(&self, group: &str) -> Option<(usize, usize)> {
+        let header = format!("[{}]", group);
+        let start = self.lines.iter().position(|line| line.trim() == header)? + 1;
+        let end = self.lines[start..]
+            .iter()
+            .position(|line| line.starts_with('['))
+            .map_or(self.lines.len(), |position| start + position);
+        Some((start, end))
+    }
+
+    fn key_positions(&self, start: usize, end: usize, key: &str) -> Vec<usize> {
+        (start..end)
+            .filter(|&index| {
+                split_line(&self.lines[index]).is_some_and(|(line_key, _)| line_key == key)
+            })
+            .collect()
+    }
+}
+
+impl Display for KeyFile {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        for line in &self.lines {
+            writeln!(f, "{}", line)?;
+        }
+        Ok(())
+    }
+}
+
+fn split_line(line: &str) -> Option<(&str, &str)> {
+    if line.starts_with('#') || line.starts_with('[') {
+        return None;
+    }
+    line.split_once('=')
+        .map(|(key, value)| (key.trim(), value.trim()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    static CONTENTS: &str = "# A comment
+[Desktop Entry]
+Type=Application
+# The name
+Name=Foo
+Exec=foo
+
+[Desktop Action new]
+Exec=foo --new
+";
+
+    #[test]
+    fn test_set() {
+        let mut key_file = KeyFile::parse(CONTENTS);
+        key_file.set(DESKTOP_ENTRY_GROUP, "Name", "Bar");
+        key_file.set(DESKTOP_ENTRY_GROUP, "NoDisplay", "true");
+        key_file.set("Desktop Action new", "Name", "New");
+
+        assert_eq!(key_file.get(DESKTOP_ENTRY_GROUP, "Name"), Some("Bar"));
+        assert_eq!(
+            key_file.get("Desktop Action new", "Exec"),
+            Some("foo --new")
+        );
+        assert_eq!(
+            key_file.to_string(),
+            "# A comment
+[Desktop Entry]
+Type=Application
+# The name
+Name=Bar
+Exec=foo
+NoDisplay=true
+
+[Desktop Action new]
+Exec=foo --new
+Name=New
+"
+        );
+
+        let mut key_file = KeyFile::parse("[Desktop Entry]\nName=A\nName=B\n");
+        key_file.set(DESKTOP_ENTRY_GROUP, "Name", "C");
+        key_file.set("Other", "Key", "value");
+        assert_eq!(
+            key_file.to_string(),
+            "[Desktop Entry]\nName=C\n\n[Other]\nKey=value\n"
+        );
+    }
+
+    #[test]
+    fn test_unset() {
+        let mut key_file = KeyFile::parse(CONTENTS);
+        assert!(key_file.unset(DESKTOP_ENTRY_GROUP, "Exec"));
+        assert!(!key_file.unset(DESKTOP_ENTRY_GROUP, "Icon"));
+
+        assert_eq!(key_file.get(DESKTOP_ENTRY_GROUP, "Exec"), None);
+        assert_eq!(
+            key_file.get("Desktop Action new", "Exec"),
+            Some("foo --new")
+        );
+    }
+}
@@ -0,0 +1,26 @@
+pub mod autostart;
+pub mod cache;
+pub mod categories;
+pub mod checker;
+pub mod configuration;
+pub mod create;
+pub mod daemon;
+pub mod discovery;
+pub mod entry;
+pub mod exec;
+pub mod history;
+pub mod keyfile;
+pub mod menu;
+pub mod mime;
+pub mod mimedb;
+pub mod open;
+pub mod output;
+pub mod overrides;
+pub mod parallel;
+pub mod search;
+pub mod sort;
+#[cfg(test)]
+mod tempdir;
+pub mod validate;
+pub mod watch;
+pub mod xdg;
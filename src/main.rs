@@ -1,107 +1,407 @@
-use crate::checker::Checker;
-use crate::configuration::Configuration;
-use crate::entry::Entry;
-use std::collections::HashMap;
-use std::fs;
-use std::path::{Path, PathBuf};
-use std::{
-    env,
-    io::{self, BufWriter, Write},
+use desktopentries::cache::Index;
+use desktopentries::checker::Checker;
+use desktopentries::configuration::Configuration;
+use desktopentries::daemon::{self, Client};
+use desktopentries::history::{self, History};
+use desktopentries::output::{Format, MatchedEntry, Printer};
+use desktopentries::search::Ranking;
+use desktopentries::sort::{self, SortKey};
+use desktopentries::watch::{self, Watcher};
+use desktopentries::{
+    autostart, categories, discovery, exec, menu, parallel, search, validate, xdg,
 };
+use std::collections::HashSet;
+use std::env;
+use std::io::{self, BufRead, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::time::Duration;
 use structopt::StructOpt;
 
-mod checker;
-mod configuration;
-mod entry;
+mod commands;
 
 fn main() {
-    let conf = Configuration::from_args();
+    let mut conf = Configuration::from_args();
+    if let Some(command) = conf.command.take() {
+        let dirs = discovery::application_dirs(
+            &conf.data_dirs,
+            &conf.applications_dirs,
+            conf.data_dir_mode,
+        );
+        let jobs = conf.jobs.unwrap_or_else(parallel::default_jobs);
+        commands::run(command, &dirs, jobs);
+        return;
+    }
+
+    let format = conf.format;
+    let with_generic_name = conf.with_generic_name;
+    let launch_id = conf.launch_id.take();
+    let search_term = conf.search.take();
+    let sort_key = conf.sort.take();
+    let reverse = conf.reverse;
+    let limit = conf.limit;
+    let count = conf.count;
+    let files = std::mem::take(&mut conf.files);
+    let data_dirs = std::mem::take(&mut conf.data_dirs);
+    let applications_dirs = std::mem::take(&mut conf.applications_dirs);
+    let data_dir_mode = conf.data_dir_mode;
+    let jobs = conf.jobs.unwrap_or_else(parallel::default_jobs);
+    let use_cache = conf.cache;
+    let autostart = conf.autostart;
+    let category_menu = conf.category_menu;
+    let directory = conf.directory;
+    let warn = conf.warn;
+    let extra_keys = if conf.lazy && launch_id.is_none() {
+        Some(lazy_keys(
+            format,
+            search_term.is_some(),
+            sort_key.as_ref(),
+            autostart,
+            category_menu.is_some(),
+        ))
+    } else {
+        None
+    };
+    let extra_keys = extra_keys.as_deref();
+    let watch = conf.watch;
+    let debounce = Duration::from_millis(conf.debounce);
+    let serve = conf.serve;
+    let client = conf.client;
+    let socket = conf.socket.take().or_else(daemon::default_socket_path);
     let checker = Checker::new(conf);
 
-    let mut entries = HashMap::new();
-    let xdg_data_dirs = env::var("XDG_DATA_DIRS");
-    match xdg_data_dirs {
-        Ok(value) => {
-            for dir in value.split(':') {
-                let mut path = PathBuf::from(dir);
-                path.push("applications/");
+    let dirs = if autostart {
+        autostart::autostart_dirs()
+    } else if files.is_empty() {
+        discovery::application_dirs(&data_dirs, &applications_dirs, data_dir_mode)
+    } else {
+        Vec::new()
+    };
+    let directory_dirs = if directory && !autostart {
+        discovery::directory_dirs(&dirs)
+    } else {
+        Vec::new()
+    };
+    let watched_dirs = [&dirs[..], &directory_dirs[..]].concat();
+    let sources = Sources {
+        files: &files,
+        dirs: &dirs,
+        directory_dirs: &directory_dirs,
+    };
 
-                get_entries_from_path(&path.display().to_string(), path.as_path(), &mut entries);
-            }
+    if serve {
+        let socket = socket_path(socket);
+        let load = || load_matches(&sources, use_cache, true, &checker, None, autostart, jobs);
+        if let Err(error) = daemon::serve(&socket, &watched_dirs, debounce, load) {
+            eprintln!("Could not serve queries on {}: {}", socket.display(), error);
+            process::exit(1);
         }
-        Err(_) => {
-            let base_path = "/usr/share/applications/";
-            eprintln!("$XDG_DATA_DIRS not set, defaulting to {}", base_path);
+        return;
+    }
+
+    // The cache does not store the contents, they are read again only when they are needed.
+    let with_contents = format == Format::Entries || warn || watch;
+    let history = History::load_default();
+    let collation_locale = sort::collation_locale();
+    let ranking = Ranking {
+        search_term: search_term.as_deref(),
+        sort_key: sort_key.as_ref(),
+        reverse,
+        limit,
+        collation_locale: &collation_locale,
+    };
+    let reload = || {
+        let (mut matches, errors) = load_matches(
+            &sources,
+            use_cache,
+            with_contents,
+            &checker,
+            extra_keys,
+            autostart,
+            jobs,
+        );
+        search::rank_entries(&mut matches, &ranking, checker.language_strings(), &history);
+        (matches, errors)
+    };
 
-            get_entries_from_path(&String::from(base_path), Path::new(base_path), &mut entries);
+    let (matches, errors) = if client {
+        query_server(&socket_path(socket))
+    } else {
+        reload()
+    };
+    for error in &errors {
+        eprintln!("{}", error);
+    }
+    let mut had_errors = !errors.is_empty();
+    if warn {
+        for matched in &matches {
+            for warning in validate::warnings(&matched.contents) {
+                eprintln!("Warning: {} in {}", warning, matched.path.display());
+                had_errors = true;
+            }
         }
     }
 
-    let stdout = io::stdout();
-    let mut out_handle = BufWriter::new(stdout);
-
-    for entry_path in entries.values() {
-        let contents = fs::read_to_string(entry_path);
-        match contents {
-            Ok(contents) => {
-                let entry = Entry::new(&contents);
-                match entry {
-                    Ok(entry) => {
-                        if checker.check_entry(&entry) {
-                            let out = writeln!(out_handle, "{}", entry_path.display());
-                            match out {
-                                Ok(_) => {
-                                    let out = writeln!(out_handle, "{}", contents);
-                                    if let Err(_) = out {
-                                        eprintln!("Error while outputting to stdout");
-                                    }
-                                }
-                                Err(_) => eprintln!("Error while outputting to stdout"),
-                            }
-                        }
-                    }
-                    Err(error) => eprintln!("{} in {}", error, entry_path.display()),
+    let printer = Printer::new(format, checker.language_strings(), with_generic_name);
+
+    if let Some(line) = launch_id {
+        launch_line(
+            &line,
+            &matches,
+            &printer,
+            checker.language_strings(),
+            history,
+        );
+        return;
+    }
+
+    {
+        let stdout = io::stdout();
+        let mut out_handle = BufWriter::new(stdout);
+
+        if let Some(menu_format) = category_menu {
+            let menu = categories::category_menu(
+                &matches,
+                &xdg::current_desktops(),
+                checker.language_strings(),
+            );
+            if menu::write_menu(&menu, menu_format, &mut out_handle).is_err() {
+                eprintln!("Error while outputting to stdout");
+            }
+        } else if count {
+            if writeln!(out_handle, "{}", matches.len()).is_err() {
+                eprintln!("Error while outputting to stdout");
+            }
+        } else {
+            for matched in &matches {
+                if printer.write(&mut out_handle, matched).is_err() {
+                    eprintln!("Error while outputting to stdout");
                 }
             }
-            Err(_) => eprintln!("Could not read file {}", entry_path.display()),
         }
     }
+
+    if watch {
+        if let Err(error) =
+            watch_entries(&watched_dirs, debounce, &printer, matches, errors, reload)
+        {
+            eprintln!("Could not watch the directories: {}", error);
+            process::exit(1);
+        }
+    }
+
+    if had_errors && !files.is_empty() {
+        process::exit(1);
+    }
 }
 
-fn get_entries_from_path(base_path: &String, path: &Path, entries: &mut HashMap<String, PathBuf>) {
-    let dir_iterator = fs::read_dir(path);
-    match dir_iterator {
-        Ok(dir_iterator) => {
-            for file in dir_iterator {
-                match file {
-                    Ok(file) => {
-                        let file_type = file.file_type();
-                        match file_type {
-                            Ok(file_type) => {
-                                if file_type.is_dir() {
-                                    get_entries_from_path(
-                                        base_path,
-                                        file.path().as_path(),
-                                        entries,
-                                    );
-                                } else if file_type.is_file() {
-                                    let file_name = file.path().display().to_string();
-                                    if file_name.ends_with(".desktop") {
-                                        let key =
-                                            file_name.replace(base_path, "").replace('/', "-");
-                                        entries.entry(key).or_insert(file.path());
-                                    }
-                                }
-                            }
-                            Err(_) => eprintln!("Could not get file type of {}", file.path()
-                                .display()),
-                        }
-                    }
-                    Err(_) => eprintln!("There was an error while iterating over folder contents \
-                    of {}", path.display()),
-                }
+/// The files specified on the command line, or the directories the entries are collected from
+/// with the desktop-directories directories if .directory files are queried.
+struct Sources<'a> {
+    files: &'a [PathBuf],
+    dirs: &'a [PathBuf],
+    directory_dirs: &'a [PathBuf],
+}
+
+/// Loads the matching entries. In autostart mode, only the entries started at login are kept.
+fn load_matches(
+    sources: &Sources,
+    use_cache: bool,
+    with_contents: bool,
+    checker: &Checker,
+    extra_keys: Option<&[&str]>,
+    autostart: bool,
+    jobs: usize,
+) -> (Vec<MatchedEntry>, Vec<String>) {
+    let (mut matches, errors) =
+        load_all_matches(sources, use_cache, with_contents, checker, extra_keys, jobs);
+    if autostart {
+        let desktops = xdg::current_desktops();
+        matches.retain(|matched| autostart::will_start(&matched.entry, &desktops));
+    }
+    (matches, errors)
+}
+
+fn load_all_matches(
+    sources: &Sources,
+    use_cache: bool,
+    with_contents: bool,
+    checker: &Checker,
+    extra_keys: Option<&[&str]>,
+    jobs: usize,
+) -> (Vec<MatchedEntry>, Vec<String>) {
+    if !sources.files.is_empty() {
+        let files = discovery::get_entries_from_arguments(sources.files);
+        return discovery::load_entries(files, checker, extra_keys, jobs);
+    }
+
+    let (mut matches, mut errors) = if use_cache {
+        let mut index = Index::load_default();
+        index.refresh(sources.dirs, jobs);
+        if let Err(error) = index.save() {
+            eprintln!("Could not save the index cache: {}", error);
+        }
+        index.load_entries(sources.dirs, checker, with_contents)
+    } else {
+        let entries = discovery::get_entries_from_dirs(sources.dirs, jobs)
+            .into_iter()
+            .collect();
+        discovery::load_entries(entries, checker, extra_keys, jobs)
+    };
+    if !sources.directory_dirs.is_empty() {
+        let directories = discovery::get_directory_entries(sources.directory_dirs, jobs)
+            .into_iter()
+            .collect();
+        let (directory_matches, directory_errors) =
+            discovery::load_entries(directories, checker, extra_keys, jobs);
+        matches.extend(directory_matches);
+        errors.extend(directory_errors);
+    }
+    (matches, errors)
+}
+
+fn socket_path(socket: Option<PathBuf>) -> PathBuf {
+    match socket {
+        Some(socket) => socket,
+        None => {
+            eprintln!("$XDG_RUNTIME_DIR not set, specify the socket using the --socket option");
+            process::exit(1);
+        }
+    }
+}
+
+/// Sends the arguments of this invocation, except the ones selecting the server, as a query to
+/// the server.
+fn query_server(socket: &Path) -> (Vec<MatchedEntry>, Vec<String>) {
+    let mut args = Vec::new();
+    let mut arguments = env::args().skip(1);
+    while let Some(argument) = arguments.next() {
+        if argument == "--socket" {
+            arguments.next();
+        } else if argument != "--client" && !argument.starts_with("--socket=") {
+            args.push(argument);
+        }
+    }
+
+    let response = Client::connect(socket).and_then(|mut client| client.query(&args));
+    match response {
+        Ok(response) => {
+            if let Some(error) = response.error {
+                eprintln!("{}", error);
+                process::exit(1);
             }
+            (response.entries, response.errors)
+        }
+        Err(error) => {
+            eprintln!(
+                "Could not query the server on {}: {}",
+                socket.display(),
+                error
+            );
+            process::exit(1);
+        }
+    }
+}
+
+/// Watches the directories and prints the changes of the matching entries after every rescan.
+/// Parse errors are printed only when they first occur.
+fn watch_entries<F>(
+    dirs: &[PathBuf],
+    debounce: Duration,
+    printer: &Printer,
+    mut matches: Vec<MatchedEntry>,
+    errors: Vec<String>,
+    reload: F,
+) -> io::Result<()>
+where
+    F: Fn() -> (Vec<MatchedEntry>, Vec<String>),
+{
+    let mut watcher = Watcher::new(dirs)?;
+    let mut reported_errors: HashSet<String> = errors.into_iter().collect();
+
+    loop {
+        watcher.wait(debounce)?;
+        let (new_matches, errors) = reload();
+        for error in errors {
+            if !reported_errors.contains(&error) {
+                eprintln!("{}", error);
+                reported_errors.insert(error);
+            }
+        }
+
+        {
+            let stdout = io::stdout();
+            let mut out_handle = BufWriter::new(stdout.lock());
+            for (change, matched) in watch::diff(&matches, &new_matches) {
+                write!(out_handle, "{}\t", change)?;
+                printer.write(&mut out_handle, matched)?;
+            }
+            out_handle.flush()?;
+        }
+        matches = new_matches;
+    }
+}
+
+/// Returns the keys which have to be parsed besides the ones read by the checks.
+fn lazy_keys(
+    format: Format,
+    search: bool,
+    sort_key: Option<&SortKey>,
+    autostart: bool,
+    category_menu: bool,
+) -> Vec<&str> {
+    let mut keys = Vec::new();
+    if autostart {
+        keys.extend(autostart::KEYS);
+    }
+    if category_menu {
+        keys.extend(categories::KEYS);
+    }
+    if format != Format::Entries {
+        keys.extend(&["Name", "GenericName", "Icon"]);
+    }
+    if search {
+        keys.extend(&["Name", "GenericName", "Keywords", "Comment", "Exec"]);
+    }
+    match sort_key {
+        Some(SortKey::Name) => keys.push("Name"),
+        Some(SortKey::Key(key)) => keys.push(key),
+        _ => {}
+    }
+    keys
+}
+
+fn launch_line(
+    line: &str,
+    matches: &[MatchedEntry],
+    printer: &Printer,
+    language_strings: &[String],
+    mut history: History,
+) {
+    let mut line = String::from(line);
+    if line == "-" {
+        line.clear();
+        if io::stdin().lock().read_line(&mut line).is_err() {
+            eprintln!("Could not read the line to launch from stdin");
+            process::exit(1);
+        }
+    }
+
+    match printer.find(&line, matches) {
+        Some(matched) => {
+            if let Err(error) = exec::launch(&matched.entry, &matched.path, &[], language_strings) {
+                eprintln!("{} in {}", error, matched.path.display());
+                process::exit(1);
+            }
+
+            history.record(&matched.id, history::now());
+            if let Err(error) = history.save() {
+                eprintln!("Could not save the launch history: {}", error);
+            }
+        }
+        None => {
+            eprintln!("Could not find an entry matching {}", line.trim_end());
+            process::exit(1);
         }
-        Err(_) => eprintln!("Could not read path {}", path.display()),
     }
 }
@@ -0,0 +1,994 @@
+use core::fmt;
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::env;
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+use std::fs;
+use std::mem;
+use std::path::{Path, PathBuf};
+
+use roxmltree::{Document, Node, ParsingOptions};
+
+use super::categories;
+use super::discovery;
+use super::entry::Entry;
+use super::exec;
+use super::parallel;
+use super::xdg;
+
+mod format;
+
+pub use format::{write_menu, write_tree, MenuFormat, MENU_FORMATS};
+
+/// A menu of the resulting menu tree.
+#[derive(Debug, Serialize)]
+pub struct Menu {
+    pub name: String,
+    pub label: String,
+    pub icon: Option<String>,
+    pub directory: Option<PathBuf>,
+    pub items: Vec<MenuItem>,
+}
+
+/// An application shown in a menu.
+#[derive(Debug, Clone, Serialize)]
+pub struct MenuEntry {
+    pub id: String,
+    pub label: String,
+    pub icon: Option<String>,
+    pub exec: Option<String>,
+    pub command: Option<String>,
+    pub path: PathBuf,
+}
+
+/// An item of a menu in the order given by its layout.
+#[derive(Debug, Serialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum MenuItem {
+    Menu(Menu),
+    Entry(MenuEntry),
+    Separator,
+}
+
+impl MenuItem {
+    pub fn label(&self) -> &str {
+        match self {
+            MenuItem::Menu(menu) => &menu.label,
+            MenuItem::Entry(entry) => &entry.label,
+            MenuItem::Separator => "",
+        }
+    }
+
+    fn is_separator(&self) -> bool {
+        matches!(self, MenuItem::Separator)
+    }
+}
+
+/// Returns the applications menu file, the first ${XDG_MENU_PREFIX}applications.menu found in
+/// the menus subdirectories of $XDG_CONFIG_HOME and $XDG_CONFIG_DIRS.
+pub fn menu_file() -> Option<PathBuf> {
+    let prefix = env::var("XDG_MENU_PREFIX").unwrap_or_default();
+    xdg::config_dirs()
+        .into_iter()
+        .map(|dir| {
+            dir.join("menus")
+                .join(format!("{}applications.menu", prefix))
+        })
+        .find(|path| path.is_file())
+}
+
+/// Builds the menu tree described by the menu file as specified by the Desktop Menu
+/// Specification. The applications directories, in the order of their precedence, are used for
+/// <DefaultAppDirs>, their desktop-directories counterparts for <DefaultDirectoryDirs>. Entries
+/// not shown in the desktop environments are left out, the labels are localized according to the
+/// language strings.
+pub fn load(
+    path: &Path,
+    application_dirs: &[PathBuf],
+    desktops: &[String],
+    language_strings: &[String],
+    jobs: usize,
+) -> Result<Menu, MenuError> {
+    let config_dirs = xdg::config_dirs();
+    let merged_dir_name = format!(
+        "{}-merged",
+        path.file_stem().unwrap_or_default().to_string_lossy()
+    );
+    let mut loader = Loader {
+        config_dirs: &config_dirs,
+        default_app_dirs: application_dirs.iter().rev().cloned().collect(),
+        default_directory_dirs: discovery::directory_dirs(application_dirs)
+            .into_iter()
+            .rev()
+            .collect(),
+        merged_dir_name,
+        loading: Vec::new(),
+    };
+    let mut root = MenuNode::default();
+    loader.load_file(path, &mut root, false)?;
+    consolidate(&mut root);
+
+    let mut resolver = Resolver {
+        desktops,
+        language_strings,
+        jobs,
+        pools: HashMap::new(),
+        allocated: HashSet::new(),
+    };
+    resolver.select(&mut root, &[], false);
+    resolver.select(&mut root, &[], true);
+
+    let name = root.name.clone();
+    Ok(resolver
+        .build(root, &[], &Layout::default())
+        .unwrap_or_else(|| Menu {
+            label: name.clone(),
+            name,
+            icon: None,
+            directory: None,
+            items: Vec::new(),
+        }))
+}
+
+/// A rule of <Include> and <Exclude> elements.
+#[derive(Debug, PartialEq)]
+enum Rule {
+    Filename(String),
+    Category(String),
+    All,
+    And(Vec<Rule>),
+    Or(Vec<Rule>),
+    Not(Vec<Rule>),
+}
+
+impl Rule {
+    fn matches(&self, id: &str, categories: &[String]) -> bool {
+        match self {
+            Rule::Filename(filename) => filename == id,
+            Rule::Category(category) => categories.contains(category),
+            Rule::All => true,
+            Rule::And(rules) => rules.iter().all(|rule| rule.matches(id, categories)),
+            Rule::Or(rules) => rules.iter().any(|rule| rule.matches(id, categories)),
+            Rule::Not(rules) => !rules.iter().any(|rule| rule.matches(id, categories)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum LayoutItem {
+    Filename(String),
+    Menuname(String),
+    Separator,
+    Merge(MergeType),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum MergeType {
+    Menus,
+    Files,
+    All,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Layout {
+    items: Vec<LayoutItem>,
+    show_empty: Option<bool>,
+}
+
+impl Default for Layout {
+    /// Submenus followed by entries, both sorted by their labels.
+    fn default() -> Self {
+        Layout {
+            items: vec![
+                LayoutItem::Merge(MergeType::Menus),
+                LayoutItem::Merge(MergeType::Files),
+            ],
+            show_empty: Some(false),
+        }
+    }
+}
+
+/// A <Menu> element. The directories and the .directory files are in the increasing order of
+/// their priority, as later elements take precedence.
+#[derive(Debug, Default)]
+struct MenuNode {
+    name: String,
+    app_dirs: Vec<PathBuf>,
+    directory_dirs: Vec<PathBuf>,
+    directories: Vec<String>,
+    only_unallocated: Option<bool>,
+    deleted: Option<bool>,
+    rules: Vec<(bool, Rule)>,
+    moves: Vec<(String, String)>,
+    layout: Option<Layout>,
+    default_layout: Option<Layout>,
+    submenus: Vec<MenuNode>,
+    selected: Vec<MenuEntry>,
+}
+
+/// Reads menu files, resolving the merged files and directories into a single tree.
+struct Loader<'a> {
+    config_dirs: &'a [PathBuf],
+    default_app_dirs: Vec<PathBuf>,
+    default_directory_dirs: Vec<PathBuf>,
+    merged_dir_name: String,
+    loading: Vec<PathBuf>,
+}
+
+impl Loader<'_> {
+    /// Parses the menu file into the node. The <Name> of merged files is ignored.
+    fn load_file(
+        &mut self,
+        path: &Path,
+        node: &mut MenuNode,
+        merged: bool,
+    ) -> Result<(), MenuError> {
+        let error = |message: String| MenuError::new(format!("{} in {}", message, path.display()));
+        let contents = fs::read_to_string(path).map_err(|error| {
+            MenuError::new(format!("Could not read {}: {}", path.display(), error))
+        })?;
+        let options = ParsingOptions {
+            allow_dtd: true,
+            ..ParsingOptions::default()
+        };
+        let document =
+            Document::parse_with_options(&contents, options).map_err(|e| error(e.to_string()))?;
+        let root = document.root_element();
+        if root.tag_name().name() != "Menu" {
+            return Err(error(String::from("The root element is not <Menu>")));
+        }
+
+        self.loading
+            .push(fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf()));
+        self.parse_menu(root, path, node, merged);
+        self.loading.pop();
+        Ok(())
+    }
+
+    /// Merges the menu file into the node, ignoring missing files and files already being
+    /// loaded, which would merge themselves endlessly.
+    fn merge_file(&mut self, path: &Path, node: &mut MenuNode) {
+        if !path.is_file() {
+            return;
+        }
+        let canonical = fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+        if self.loading.contains(&canonical) {
+            eprintln!("Skipping {} as it is merged recursively", path.display());
+            return;
+        }
+        if let Err(error) = self.load_file(path, node, true) {
+            eprintln!("{}", error);
+        }
+    }
+
+    /// Merges the .menu files of the directory in alphabetical order.
+    fn merge_dir(&mut self, dir: &Path, node: &mut MenuNode) {
+        let mut files: Vec<PathBuf> = match fs::read_dir(dir) {
+            Ok(entries) => entries
+                .filter_map(|entry| entry.ok())
+                .map(|entry| entry.path())
+                .filter(|path| {
+                    path.extension()
+                        .is_some_and(|extension| extension == "menu")
+                })
+                .collect(),
+            Err(_) => return,
+        };
+        files.sort();
+        for file in files {
+            self.merge_file(&file, node);
+        }
+    }
+
+    /// Returns the file with the same path relative to a configuration directory as the menu
+    /// file, located in one of the directories with a lower precedence.
+    fn parent_file(&self, file: &Path) -> Option<PathBuf> {
+        let (index, relative) = self
+            .config_dirs
+            .iter()
+            .enumerate()
+            .find_map(|(index, dir)| Some((index, file.strip_prefix(dir).ok()?)))?;
+        self.config_dirs[index + 1..]
+            .iter()
+            .map(|dir| dir.join(relative))
+            .find(|path| path.is_file())
+    }
+
+    fn parse_menu(&mut self, element: Node, file: &Path, node: &mut MenuNode, merged: bool) {
+        let base = file.parent().unwrap_or_else(|| Path::new(""));
+        for child in element.children().filter(|child| child.is_element()) {
+            match child.tag_name().name() {
+                "Name" if !merged => node.name = text(child),
+                "AppDir" => node.app_dirs.push(base.join(text(child))),
+                "DefaultAppDirs" => node.app_dirs.extend(self.default_app_dirs.iter().cloned()),
+                "DirectoryDir" => node.directory_dirs.push(base.join(text(child))),
+                "DefaultDirectoryDirs" => node
+                    .directory_dirs
+                    .extend(self.default_directory_dirs.iter().cloned()),
+                "Directory" => node.directories.push(text(child)),
+                "OnlyUnallocated" => node.only_unallocated = Some(true),
+                "NotOnlyUnallocated" => node.only_unallocated = Some(false),
+                "Deleted" => node.deleted = Some(true),
+                "NotDeleted" => node.deleted = Some(false),
+                "Include" => node.rules.push((true, Rule::Or(parse_rules(child)))),
+                "Exclude" => node.rules.push((false, Rule::Or(parse_rules(child)))),
+                "Menu" => {
+                    let mut submenu = MenuNode::default();
+                    self.parse_menu(child, file, &mut submenu, false);
+                    node.submenus.push(submenu);
+                }
+                "MergeFile" => {
+                    let path = if child.attribute("type") == Some("parent") {
+                        self.parent_file(file)
+                    } else {
+                        Some(base.join(text(child)))
+                    };
+                    if let Some(path) = path {
+                        self.merge_file(&path, node);
+                    }
+                }
+                "MergeDir" => self.merge_dir(&base.join(text(child)), node),
+                "DefaultMergeDirs" => {
+                    let dirs: Vec<PathBuf> = self
+                        .config_dirs
+                        .iter()
+                        .rev()
+                        .map(|dir| dir.join("menus").join(&self.merged_dir_name))
+                        .collect();
+                    for dir in dirs {
+                        self.merge_dir(&dir, node);
+                    }
+                }
+                "Move" => {
+                    let path = |name: &str| {
+                        child
+                            .children()
+                            .find(|path| path.has_tag_name(name))
+                            .map(text)
+                    };
+                    if let (Some(old), Some(new)) = (path("Old"), path("New")) {
+                        node.moves.push((old, new));
+                    }
+                }
+                "Layout" => node.layout = Some(parse_layout(child)),
+                "DefaultLayout" => node.default_layout = Some(parse_layout(child)),
+                _ => {}
+            }
+        }
+    }
+}
+
+fn text(node: Node) -> String {
+    node.text().unwrap_or_default().trim().to_string()
+}
+
+fn parse_rules(element: Node) -> Vec<Rule> {
+    element
+        .children()
+        .filter(|child| child.is_element())
+        .filter_map(|child| match child.tag_name().name() {
+            "Filename" => Some(Rule::Filename(text(child))),
+            "Category" => Some(Rule::Category(text(child))),
+            "All" => Some(Rule::All),
+            "And" => Some(Rule::And(parse_rules(child))),
+            "Or" => Some(Rule::Or(parse_rules(child))),
+            "Not" => Some(Rule::Not(parse_rules(child))),
+            _ => None,
+        })
+        .collect()
+}
+
+fn parse_layout(element: Node) -> Layout {
+    let items = element
+        .children()
+        .filter(|child| child.is_element())
+        .filter_map(|child| match child.tag_name().name() {
+            "Filename" => Some(LayoutItem::Filename(text(child))),
+            "Menuname" => Some(LayoutItem::Menuname(text(child))),
+            "Separator" => Some(LayoutItem::Separator),
+            "Merge" => match child.attribute("type") {
+                Some("menus") => Some(LayoutItem::Merge(MergeType::Menus)),
+                Some("files") => Some(LayoutItem::Merge(MergeType::Files)),
+                Some("all") => Some(LayoutItem::Merge(MergeType::All)),
+                _ => None,
+            },
+            _ => None,
+        })
+        .collect();
+    Layout {
+        items,
+        show_empty: element
+            .attribute("show_empty")
+            .map(|show_empty| show_empty == "true"),
+    }
+}
+
+/// Merges the submenus with the same name and performs the moves, recursively.
+fn consolidate(node: &mut MenuNode) {
+    for submenu in mem::take(&mut node.submenus) {
+        add_submenu(node, submenu);
+    }
+    for (old, new) in mem::take(&mut node.moves) {
+        let old: Vec<&str> = old.split('/').filter(|name| !name.is_empty()).collect();
+        let new: Vec<&str> = new.split('/').filter(|name| !name.is_empty()).collect();
+        if let Some(menu) = take_submenu(node, &old) {
+            insert_submenu(node, &new, menu);
+        }
+    }
+    for submenu in &mut node.submenus {
+        consolidate(submenu);
+    }
+}
+
+/// Adds the submenu, merging it into an existing submenu with the same name.
+fn add_submenu(node: &mut MenuNode, submenu: MenuNode) {
+    match node
+        .submenus
+        .iter_mut()
+        .find(|existing| existing.name == submenu.name)
+    {
+        Some(existing) => merge_nodes(existing, submenu),
+        None => node.submenus.push(submenu),
+    }
+}
+
+/// Appends the contents of the later node, whose flags and layouts take precedence.
+fn merge_nodes(node: &mut MenuNode, later: MenuNode) {
+    node.app_dirs.extend(later.app_dirs);
+    node.directory_dirs.extend(later.directory_dirs);
+    node.directories.extend(later.directories);
+    node.only_unallocated = later.only_unallocated.or(node.only_unallocated);
+    node.deleted = later.deleted.or(node.deleted);
+    node.rules.extend(later.rules);
+    node.moves.extend(later.moves);
+    node.layout = later.layout.or(node.layout.take());
+    node.default_layout = later.default_layout.or(node.default_layout.take());
+    node.submenus.extend(later.submenus);
+}
+
+fn take_submenu(node: &mut MenuNode, path: &[&str]) -> Option<MenuNode> {
+    let (name, rest) = path.split_first()?;
+    if !rest.is_empty() {
+        return node
+            .submenus
+            .iter_mut()
+            .filter(|submenu| submenu.name == *name)
+            .find_map(|submenu| take_submenu(submenu, rest));
+    }
+
+    let mut taken: Option<MenuNode> = None;
+    for submenu in mem::take(&mut node.submenus) {
+        if submenu.name != *name {
+            node.submenus.push(submenu);
+        } else if let Some(taken) = &mut taken {
+            merge_nodes(taken, submenu);
+        } else {
+            taken = Some(submenu);
+        }
+    }
+    taken
+}
+
+fn insert_submenu(node: &mut MenuNode, path: &[&str], mut menu: MenuNode) {
+    let (name, rest) = match path.split_first() {
+        Some(split) => split,
+        None => return,
+    };
+    if rest.is_empty() {
+        menu.name = name.to_string();
+        add_submenu(node, menu);
+        return;
+    }
+
+    let index = match node
+        .submenus
+        .iter()
+        .position(|submenu| submenu.name == *name)
+    {
+        Some(index) => index,
+        None => {
+            node.submenus.push(MenuNode {
+                name: name.to_string(),
+                ..MenuNode::default()
+            });
+            node.submenus.len() - 1
+        }
+    };
+    insert_submenu(&mut node.submenus[index], rest, menu);
+}
+
+/// An entry which may be included in menus.
+struct Candidate {
+    entry: MenuEntry,
+    categories: Vec<String>,
+}
+
+/// The entries found in a list of applications directories, by their IDs.
+type Pool = BTreeMap<String, Candidate>;
+
+/// Selects the entries of the menus and builds the resulting tree.
+struct Resolver<'a> {
+    desktops: &'a [String],
+    language_strings: &'a [String],
+    jobs: usize,
+    pools: HashMap<Vec<PathBuf>, Pool>,
+    allocated: HashSet<String>,
+}
+
+impl Resolver<'_> {
+    /// Selects the entries of the menus which are, or with unallocated set, are not
+    /// <OnlyUnallocated>. The applications directories are inherited from the parent menus.
+    fn select(&mut self, node: &mut MenuNode, app_dirs: &[PathBuf], unallocated: bool) {
+        if node.deleted == Some(true) {
+            return;
+        }
+        let mut dirs = app_dirs.to_vec();
+        dirs.extend(node.app_dirs.iter().cloned());
+
+        if node.only_unallocated.unwrap_or(false) == unallocated {
+            let pool = pool(
+                &mut self.pools,
+                &dirs,
+                self.desktops,
+                self.language_strings,
+                self.jobs,
+            );
+            let allocated = &self.allocated;
+            let mut selected: BTreeMap<&str, &Candidate> = BTreeMap::new();
+            for (include, rule) in &node.rules {
+                if *include {
+                    selected.extend(
+                        pool.iter()
+                            .filter(|(id, candidate)| {
+                                rule.matches(id, &candidate.categories)
+                                    && !(unallocated && allocated.contains(id.as_str()))
+                            })
+                            .map(|(id, candidate)| (id.as_str(), candidate)),
+                    );
+                } else {
+                    selected.retain(|id, candidate| !rule.matches(id, &candidate.categories));
+                }
+            }
+            node.selected = selected
+                .into_values()
+                .map(|candidate| candidate.entry.clone())
+                .collect();
+            if !unallocated {
+                self.allocated
+                    .extend(node.selected.iter().map(|entry| entry.id.clone()));
+            }
+        }
+
+        for submenu in &mut node.submenus {
+            self.select(submenu, &dirs, unallocated);
+        }
+    }
+
+    /// Builds the menu, returning none if it is deleted, hidden by its .directory file or empty.
+    fn build(&self, node: MenuNode, directory_dirs: &[PathBuf], layout: &Layout) -> Option<Menu> {
+        if node.deleted == Some(true) {
+            return None;
+        }
+        let mut dirs = directory_dirs.to_vec();
+        dirs.extend(node.directory_dirs);
+
+        let directory = node
+            .directories
+            .iter()
+            .rev()
+            .flat_map(|name| dirs.iter().rev().map(move |dir| dir.join(name)))
+            .find_map(|path| {
+                let entry = Entry::new(&fs::read_to_string(&path).ok()?).ok()?;
+                Some((path, entry))
+            });
+        let is_true = |key: &str| {
+            directory
+                .as_ref()
+                .and_then(|(_, entry)| entry.get(key))
+                .is_some_and(|value| value == "true")
+        };
+        if is_true("NoDisplay") || is_true("Hidden") {
+            return None;
+        }
+
+        // Attributes missing in a <DefaultLayout> are inherited from the one of the parent menu,
+        // attributes missing in a <Layout> from the <DefaultLayout>.
+        let name = node.name;
+        let default_layout = match node.default_layout {
+            Some(default_layout) => Layout {
+                show_empty: default_layout.show_empty.or(layout.show_empty),
+                ..default_layout
+            },
+            None => layout.clone(),
+        };
+        let layout = node.layout.as_ref().unwrap_or(&default_layout);
+        let show_empty = layout.show_empty.or(default_layout.show_empty) == Some(true);
+        let submenus: Vec<Menu> = node
+            .submenus
+            .into_iter()
+            .filter_map(|submenu| self.build(submenu, &dirs, &default_layout))
+            .collect();
+        let items = apply_layout(layout, submenus, node.selected);
+        let is_empty = items.iter().all(MenuItem::is_separator);
+        if is_empty && !show_empty {
+            return None;
+        }
+
+        let label = directory
+            .as_ref()
+            .and_then(|(_, entry)| entry.get_localized("Name", self.language_strings))
+            .cloned()
+            .unwrap_or_else(|| name.clone());
+        let icon = directory
+            .as_ref()
+            .and_then(|(_, entry)| entry.get("Icon"))
+            .cloned();
+        Some(Menu {
+            name,
+            label,
+            icon,
+            directory: directory.map(|(path, _)| path),
+            items,
+        })
+    }
+}
+
+/// Returns the entries of the applications directories shown in the desktop environments,
+/// scanning the directories only once for every list of directories.
+fn pool<'a>(
+    pools: &'a mut HashMap<Vec<PathBuf>, Pool>,
+    dirs: &[PathBuf],
+    desktops: &[String],
+    language_strings: &[String],
+    jobs: usize,
+) -> &'a Pool {
+    pools.entry(dirs.to_vec()).or_insert_with(|| {
+        let by_precedence: Vec<PathBuf> = dirs.iter().rev().cloned().collect();
+        let sources: Vec<(String, PathBuf)> =
+            discovery::get_entries_from_dirs(&by_precedence, jobs)
+                .into_iter()
+                .collect();
+        let entries = parallel::map(&sources, jobs, |(_, path)| {
+            let entry = Entry::new(&fs::read_to_string(path).ok()?).ok()?;
+            Some(entry).filter(|entry| is_shown(entry, desktops))
+        });
+
+        let mut pool = Pool::new();
+        for ((id, path), entry) in sources.into_iter().zip(entries) {
+            let entry = match entry {
+                Some(entry) => entry,
+                None => continue,
+            };
+            let categories = categories::categories(&entry)
+                .into_iter()
+                .map(String::from)
+                .collect();
+            let menu_entry = menu_entry(&id, &path, &entry, language_strings);
+            pool.insert(
+                id,
+                Candidate {
+                    entry: menu_entry,
+                    categories,
+                },
+            );
+        }
+        pool
+    })
+}
+
+/// Returns the menu entry of the application, labeled by its localized name. The command is its
+/// Exec key expanded without files, quoted for a shell.
+pub(crate) fn menu_entry(
+    id: &str,
+    path: &Path,
+    entry: &Entry,
+    language_strings: &[String],
+) -> MenuEntry {
+    let command = exec::expand_exec(entry, path, &[], language_strings)
+        .ok()
+        .and_then(|commands| commands.into_iter().next())
+        .map(|command| exec::command_line(&command));
+    MenuEntry {
+        id: String::from(id),
+        label: entry
+            .get_localized("Name", language_strings)
+            .cloned()
+            .unwrap_or_else(|| String::from(id)),
+        icon: entry.get("Icon").cloned(),
+        exec: entry.get("Exec").cloned(),
+        command,
+        path: path.to_path_buf(),
+    }
+}
+
+/// Returns whether the application or link is shown in menus of the desktop environments.
+pub(crate) fn is_shown(entry: &Entry, desktops: &[String]) -> bool {
+    let is_true = |key: &str| entry.get(key).is_some_and(|value| value == "true");
+    let list = |key: &str| -> Vec<&str> {
+        entry
+            .get(key)
+            .map(|value| value.split(';').filter(|item| !item.is_empty()).collect())
+            .unwrap_or_default()
+    };
+
+    let only_show_in = list("OnlyShowIn");
+    let not_show_in = list("NotShowIn");
+    entry
+        .get("Type")
+        .is_some_and(|value| value == "Application" || value == "Link")
+        && !is_true("NoDisplay")
+        && !is_true("Hidden")
+        && (only_show_in.is_empty()
+            || desktops
+                .iter()
+                .any(|desktop| only_show_in.contains(&desktop.as_str())))
+        && !desktops
+            .iter()
+            .any(|desktop| not_show_in.contains(&desktop.as_str()))
+        && entry
+            .get("TryExec")
+            .is_none_or(|try_exec| exec::find_executable(try_exec).is_some())
+}
+
+/// Orders the submenus and entries according to the layout. Items not mentioned by the layout
+/// are left out, merged items are sorted by their labels. Leading, trailing and repeated
+/// separators are removed.
+fn apply_layout(layout: &Layout, menus: Vec<Menu>, entries: Vec<MenuEntry>) -> Vec<MenuItem> {
+    let mut menus: Vec<Option<Menu>> = menus.into_iter().map(Some).collect();
+    let mut entries: Vec<Option<MenuEntry>> = entries.into_iter().map(Some).collect();
+    let mut items = Vec::new();
+
+    for layout_item in &layout.items {
+        match layout_item {
+            LayoutItem::Filename(id) => {
+                if let Some(entry) = entries
+                    .iter_mut()
+                    .find(|entry| entry.as_ref().is_some_and(|entry| entry.id == *id))
+                    .and_then(Option::take)
+                {
+                    items.push(MenuItem::Entry(entry));
+                }
+            }
+            LayoutItem::Menuname(name) => {
+                if let Some(menu) = menus
+                    .iter_mut()
+                    .find(|menu| menu.as_ref().is_some_and(|menu| menu.name == *name))
+                    .and_then(Option::take)
+                {
+                    items.push(MenuItem::Menu(menu));
+                }
+            }
+            LayoutItem::Separator => items.push(MenuItem::Separator),
+            LayoutItem::Merge(merge_type) => {
+                let mut merged: Vec<MenuItem> = Vec::new();
+                if *merge_type != MergeType::Files {
+                    merged.extend(
+                        menus
+                            .iter_mut()
+                            .filter_map(Option::take)
+                            .map(MenuItem::Menu),
+                    );
+                }
+                if *merge_type != MergeType::Menus {
+                    merged.extend(
+                        entries
+                            .iter_mut()
+                            .filter_map(Option::take)
+                            .map(MenuItem::Entry),
+                    );
+                }
+                merged.sort_by_key(|item| item.label().to_lowercase());
+                items.extend(merged);
+            }
+        }
+    }
+
+    let mut cleaned: Vec<MenuItem> = Vec::with_capacity(items.len());
+    for item in items {
+        let after_separator = cleaned.last().is_none_or(MenuItem::is_separator);
+        if !item.is_separator() || !after_separator {
+            cleaned.push(item);
+        }
+    }
+    if cleaned.last().is_some_and(MenuItem::is_separator) {
+        cleaned.pop();
+    }
+    cleaned
+}
+
+#[derive(Debug)]
+pub struct MenuError {
+    message: String,
+}
+
+impl MenuError {
+    fn new(message: String) -> MenuError {
+        MenuError { message }
+    }
+}
+
+impl Error for MenuError {}
+
+impl Display for MenuError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tempdir::TempDir;
+    use std::io;
+
+    fn create_file(path: &Path, contents: &str) -> io::Result<()> {
+        fs::create_dir_all(path.parent().unwrap())?;
+        fs::write(path, contents)
+    }
+
+    fn application(name: &str, keys: &str) -> String {
+        format!(
+            "[Desktop Entry]\nType=Application\nName={}\nExec={}\n{}",
+            name,
+            name.to_lowercase(),
+            keys
+        )
+    }
+
+    fn labels(items: &[MenuItem]) -> Vec<String> {
+        items
+            .iter()
+            .map(|item| match item {
+                MenuItem::Menu(menu) => format!("{}/", menu.label),
+                MenuItem::Entry(entry) => entry.label.clone(),
+                MenuItem::Separator => String::from("-"),
+            })
+            .collect()
+    }
+
+    fn submenu<'a>(menu: &'a Menu, name: &str) -> &'a Menu {
+        menu.items
+            .iter()
+            .find_map(|item| match item {
+                MenuItem::Menu(menu) if menu.name == name => Some(menu),
+                _ => None,
+            })
+            .unwrap()
+    }
+
+    #[test]
+    fn test_load() -> Result<(), Box<dyn Error>> {
+        let root = TempDir::new("menu");
+        let applications = root.join("data/applications");
+        create_file(
+            &applications.join("editor.desktop"),
+            &application("Editor", "Categories=Utility;TextEditor;\n"),
+        )?;
+        create_file(
+            &applications.join("calc.desktop"),
+            &application("Calculator", "Categories=Utility;\n"),
+        )?;
+        create_file(
+            &applications.join("game.desktop"),
+            &application("Game", "Categories=Game;\n"),
+        )?;
+        create_file(
+            &applications.join("hidden.desktop"),
+            &application("Hidden", "Categories=Utility;\nNoDisplay=true\n"),
+        )?;
+        create_file(
+            &applications.join("kde.desktop"),
+            &application("KDE Only", "Categories=Utility;\nOnlyShowIn=KDE;\n"),
+        )?;
+        create_file(&applications.join("misc.desktop"), &application("Misc", ""))?;
+        create_file(
+            &root.join("data/desktop-directories/Utility.directory"),
+            "[Desktop Entry]\nType=Directory\nName=Accessories\nIcon=utilities\n",
+        )?;
+        create_file(
+            &root.join("menus/applications-merged/games.menu"),
+            "<Menu><Name>Merged</Name>\
+            <Menu><Name>Games</Name><Include><Category>Game</Category></Include></Menu></Menu>",
+        )?;
+        let menu_file = root.join("menus/applications.menu");
+        create_file(
+            &menu_file,
+            r#"<!DOCTYPE Menu PUBLIC "-//freedesktop//DTD Menu 1.0//EN"
+            "http://www.freedesktop.org/standards/menu-spec/menu-1.0.dtd">
+            <Menu>
+              <Name>Applications</Name>
+              <DefaultAppDirs/>
+              <DefaultDirectoryDirs/>
+              <Menu>
+                <Name>Utility</Name>
+                <Directory>Utility.directory</Directory>
+                <Include>
+                  <And><Category>Utility</Category><Not><Category>TextEditor</Category></Not></And>
+                  <Filename>editor.desktop</Filename>
+                </Include>
+                <Exclude><Filename>misc.desktop</Filename></Exclude>
+                <Layout><Filename>editor.desktop</Filename><Separator/><Merge type="files"/></Layout>
+              </Menu>
+              <MergeDir>applications-merged</MergeDir>
+              <Menu><Name>Empty</Name></Menu>
+              <Menu>
+                <Name>Shown</Name>
+                <DefaultLayout show_empty="true"><Merge type="all"/></DefaultLayout>
+                <Menu><Name>Inherited</Name><Layout><Merge type="files"/></Layout></Menu>
+                <Menu>
+                  <Name>Hidden</Name>
+                  <Layout show_empty="false"><Merge type="files"/></Layout>
+                </Menu>
+              </Menu>
+              <Menu><Name>Old</Name><Include><Filename>game.desktop</Filename></Include></Menu>
+              <Move><Old>Old</Old><New>Games/Classic</New></Move>
+              <Menu>
+                <Name>Other</Name>
+                <OnlyUnallocated/>
+                <Include><All/></Include>
+              </Menu>
+            </Menu>"#,
+        )?;
+
+        let desktops = vec![String::from("GNOME")];
+        let menu = load(&menu_file, &[applications], &desktops, &[], 1)?;
+        assert_eq!(menu.label, "Applications");
+        assert_eq!(
+            labels(&menu.items),
+            vec!["Accessories/", "Games/", "Other/", "Shown/"]
+        );
+        assert_eq!(labels(&submenu(&menu, "Shown").items), vec!["Inherited/"]);
+
+        let utility = submenu(&menu, "Utility");
+        assert_eq!(utility.icon.as_deref(), Some("utilities"));
+        assert_eq!(labels(&utility.items), vec!["Editor", "-", "Calculator"]);
+        let games = submenu(&menu, "Games");
+        assert_eq!(labels(&games.items), vec!["Classic/", "Game"]);
+        assert_eq!(labels(&submenu(games, "Classic").items), vec!["Game"]);
+        assert_eq!(labels(&submenu(&menu, "Other").items), vec!["Misc"]);
+
+        let mut tree = Vec::new();
+        write_tree(&menu, &mut tree)?;
+        assert!(String::from_utf8(tree)?
+            .starts_with("Applications\n  Accessories/\n    Editor\teditor.desktop\n    -\n"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_apply_layout() {
+        let entry = |id: &str| MenuEntry {
+            id: String::from(id),
+            label: id.to_uppercase(),
+            icon: None,
+            exec: None,
+            command: None,
+            path: PathBuf::from(id),
+        };
+        let menu = |name: &str| Menu {
+            name: String::from(name),
+            label: String::from(name),
+            icon: None,
+            directory: None,
+            items: Vec::new(),
+        };
+        let layout = Layout {
+            items: vec![
+                LayoutItem::Separator,
+                LayoutItem::Menuname(String::from("b")),
+                LayoutItem::Separator,
+                LayoutItem::Separator,
+                LayoutItem::Merge(MergeType::All),
+                LayoutItem::Separator,
+            ],
+            show_empty: None,
+        };
+
+        let items = apply_layout(
+            &layout,
+            vec![menu("b"), menu("c")],
+            vec![entry("d"), entry("a")],
+        );
+        assert_eq!(labels(&items), vec!["b/", "-", "A", "c/", "D"]);
+    }
+}
@@ -0,0 +1,360 @@
+use std::io::{self, Write};
+use std::str::FromStr;
+
+use super::{Menu, MenuEntry, MenuItem};
+
+/// The format the menu tree is printed in.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MenuFormat {
+    Tree,
+    Json,
+    Openbox,
+    Jwm,
+    Fluxbox,
+    Icewm,
+    Twm,
+}
+
+impl FromStr for MenuFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "tree" => Ok(MenuFormat::Tree),
+            "json" => Ok(MenuFormat::Json),
+            "openbox" => Ok(MenuFormat::Openbox),
+            "jwm" => Ok(MenuFormat::Jwm),
+            "fluxbox" => Ok(MenuFormat::Fluxbox),
+            "icewm" => Ok(MenuFormat::Icewm),
+            "twm" => Ok(MenuFormat::Twm),
+            _ => Err(format!("Unknown menu format {}", s)),
+        }
+    }
+}
+
+/// The names of the menu formats accepted by MenuFormat.
+pub static MENU_FORMATS: &[&str] = &["tree", "json", "openbox", "jwm", "fluxbox", "icewm", "twm"];
+
+/// Prints the menu in the format. The window manager formats print a configuration which can be
+/// included in the window manager's menu, entries without a command are left out.
+pub fn write_menu(menu: &Menu, format: MenuFormat, out: &mut impl Write) -> io::Result<()> {
+    match format {
+        MenuFormat::Tree => write_tree(menu, out),
+        MenuFormat::Json => {
+            serde_json::to_writer_pretty(&mut *out, menu)?;
+            writeln!(out)
+        }
+        MenuFormat::Openbox => write_openbox(menu, out),
+        MenuFormat::Jwm => write_jwm(menu, out),
+        MenuFormat::Fluxbox => write_fluxbox(menu, out),
+        MenuFormat::Icewm => write_icewm(&menu.items, 0, out),
+        MenuFormat::Twm => write_twm(menu, &menu.name, out),
+    }
+}
+
+/// Prints the menu tree, one item per line indented by its depth. Menus end with a slash,
+/// entries are followed by a tab separated ID and separators are printed as a dash.
+pub fn write_tree(menu: &Menu, out: &mut impl Write) -> io::Result<()> {
+    writeln!(out, "{}", menu.label)?;
+    write_items(&menu.items, 1, out)
+}
+
+fn write_items(items: &[MenuItem], depth: usize, out: &mut impl Write) -> io::Result<()> {
+    let indent = "  ".repeat(depth);
+    for item in items {
+        match item {
+            MenuItem::Menu(menu) => {
+                writeln!(out, "{}{}/", indent, menu.label)?;
+                write_items(&menu.items, depth + 1, out)?;
+            }
+            MenuItem::Entry(entry) => writeln!(out, "{}{}\t{}", indent, entry.label, entry.id)?,
+            MenuItem::Separator => writeln!(out, "{}-", indent)?,
+        }
+    }
+    Ok(())
+}
+
+/// Prints an Openbox menu file whose root menu has the ID root-menu.
+fn write_openbox(menu: &Menu, out: &mut impl Write) -> io::Result<()> {
+    writeln!(out, "<?xml version=\"1.0\" encoding=\"UTF-8\"?>")?;
+    writeln!(out, "<openbox_menu xmlns=\"http://openbox.org/3.4/menu\">")?;
+    write_openbox_menu(menu, "root-menu", 0, out)?;
+    writeln!(out, "</openbox_menu>")
+}
+
+fn write_openbox_menu(menu: &Menu, id: &str, depth: usize, out: &mut impl Write) -> io::Result<()> {
+    let indent = "  ".repeat(depth);
+    writeln!(
+        out,
+        "{}<menu id=\"{}\" label=\"{}\">",
+        indent,
+        escape_xml(id),
+        escape_xml(&menu.label)
+    )?;
+    for item in &menu.items {
+        match item {
+            MenuItem::Menu(submenu) => {
+                let id = format!("{}-{}", id, submenu.name);
+                write_openbox_menu(submenu, &id, depth + 1, out)?;
+            }
+            MenuItem::Entry(MenuEntry {
+                label,
+                command: Some(command),
+                ..
+            }) => writeln!(
+                out,
+                "{}  <item label=\"{}\"><action name=\"Execute\"><command>{}</command></action></item>",
+                indent,
+                escape_xml(label),
+                escape_xml(command)
+            )?,
+            MenuItem::Entry(_) => {}
+            MenuItem::Separator => writeln!(out, "{}  <separator/>", indent)?,
+        }
+    }
+    writeln!(out, "{}</menu>", indent)
+}
+
+/// Prints a JWM configuration holding the items of the menu, to be included in a RootMenu.
+fn write_jwm(menu: &Menu, out: &mut impl Write) -> io::Result<()> {
+    writeln!(out, "<?xml version=\"1.0\"?>")?;
+    writeln!(out, "<JWM>")?;
+    write_jwm_items(&menu.items, 1, out)?;
+    writeln!(out, "</JWM>")
+}
+
+fn write_jwm_items(items: &[MenuItem], depth: usize, out: &mut impl Write) -> io::Result<()> {
+    let indent = "  ".repeat(depth);
+    for item in items {
+        match item {
+            MenuItem::Menu(menu) => {
+                writeln!(
+                    out,
+                    "{}<Menu label=\"{}\"{}>",
+                    indent,
+                    escape_xml(&menu.label),
+                    jwm_icon(&menu.icon)
+                )?;
+                write_jwm_items(&menu.items, depth + 1, out)?;
+                writeln!(out, "{}</Menu>", indent)?;
+            }
+            MenuItem::Entry(MenuEntry {
+                label,
+                icon,
+                command: Some(command),
+                ..
+            }) => writeln!(
+                out,
+                "{}<Program label=\"{}\"{}>{}</Program>",
+                indent,
+                escape_xml(label),
+                jwm_icon(icon),
+                escape_xml(command)
+            )?,
+            MenuItem::Entry(_) => {}
+            MenuItem::Separator => writeln!(out, "{}<Separator/>", indent)?,
+        }
+    }
+    Ok(())
+}
+
+fn jwm_icon(icon: &Option<String>) -> String {
+    icon.as_ref()
+        .map(|icon| format!(" icon=\"{}\"", escape_xml(icon)))
+        .unwrap_or_default()
+}
+
+/// Prints a fluxbox menu file.
+fn write_fluxbox(menu: &Menu, out: &mut impl Write) -> io::Result<()> {
+    writeln!(out, "[begin] ({})", escape_fluxbox(&menu.label, ')'))?;
+    write_fluxbox_items(&menu.items, 1, out)?;
+    writeln!(out, "[end]")
+}
+
+fn write_fluxbox_items(items: &[MenuItem], depth: usize, out: &mut impl Write) -> io::Result<()> {
+    let indent = "  ".repeat(depth);
+    for item in items {
+        match item {
+            MenuItem::Menu(menu) => {
+                writeln!(
+                    out,
+                    "{}[submenu] ({})",
+                    indent,
+                    escape_fluxbox(&menu.label, ')')
+                )?;
+                write_fluxbox_items(&menu.items, depth + 1, out)?;
+                writeln!(out, "{}[end]", indent)?;
+            }
+            MenuItem::Entry(MenuEntry {
+                label,
+                command: Some(command),
+                ..
+            }) => writeln!(
+                out,
+                "{}[exec] ({}) {{{}}}",
+                indent,
+                escape_fluxbox(label, ')'),
+                escape_fluxbox(command, '}')
+            )?,
+            MenuItem::Entry(_) => {}
+            MenuItem::Separator => writeln!(out, "{}[separator]", indent)?,
+        }
+    }
+    Ok(())
+}
+
+/// Prints an IceWM menu file holding the items of the menu. Items without an icon use -.
+fn write_icewm(items: &[MenuItem], depth: usize, out: &mut impl Write) -> io::Result<()> {
+    let indent = "    ".repeat(depth);
+    let icon = |icon: &Option<String>| icon.clone().unwrap_or_else(|| String::from("-"));
+    for item in items {
+        match item {
+            MenuItem::Menu(menu) => {
+                writeln!(
+                    out,
+                    "{}menu \"{}\" {} {{",
+                    indent,
+                    escape_quoted(&menu.label),
+                    icon(&menu.icon)
+                )?;
+                write_icewm(&menu.items, depth + 1, out)?;
+                writeln!(out, "{}}}", indent)?;
+            }
+            MenuItem::Entry(entry) => {
+                if let Some(command) = &entry.command {
+                    writeln!(
+                        out,
+                        "{}prog \"{}\" {} {}",
+                        indent,
+                        escape_quoted(&entry.label),
+                        icon(&entry.icon),
+                        command
+                    )?;
+                }
+            }
+            MenuItem::Separator => writeln!(out, "{}separator", indent)?,
+        }
+    }
+    Ok(())
+}
+
+/// Prints the menu and its submenus as twm menus, each submenu named by its path.
+fn write_twm(menu: &Menu, name: &str, out: &mut impl Write) -> io::Result<()> {
+    writeln!(out, "menu \"{}\"", escape_quoted(name))?;
+    writeln!(out, "{{")?;
+    writeln!(out, "    \"{}\" f.title", escape_quoted(&menu.label))?;
+    let mut submenus = Vec::new();
+    for item in &menu.items {
+        match item {
+            MenuItem::Menu(submenu) => {
+                let submenu_name = format!("{}/{}", name, submenu.name);
+                writeln!(
+                    out,
+                    "    \"{}\" f.menu \"{}\"",
+                    escape_quoted(&submenu.label),
+                    escape_quoted(&submenu_name)
+                )?;
+                submenus.push((submenu, submenu_name));
+            }
+            MenuItem::Entry(MenuEntry {
+                label,
+                command: Some(command),
+                ..
+            }) => writeln!(
+                out,
+                "    \"{}\" f.exec \"{} &\"",
+                escape_quoted(label),
+                escape_quoted(command)
+            )?,
+            MenuItem::Entry(_) => {}
+            MenuItem::Separator => writeln!(out, "    \"\" f.nop")?,
+        }
+    }
+    writeln!(out, "}}")?;
+    for (submenu, submenu_name) in submenus {
+        writeln!(out)?;
+        write_twm(submenu, &submenu_name, out)?;
+    }
+    Ok(())
+}
+
+fn escape_xml(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Escapes backslashes and the character closing a fluxbox field.
+fn escape_fluxbox(value: &str, closing: char) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace(closing, &format!("\\{}", closing))
+}
+
+/// Escapes backslashes and double quotes of a double quoted string.
+fn escape_quoted(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_write_menu() -> io::Result<()> {
+        let menu = Menu {
+            name: String::from("Applications"),
+            label: String::from("Applications"),
+            icon: None,
+            directory: None,
+            items: vec![
+                MenuItem::Menu(Menu {
+                    name: String::from("Utility"),
+                    label: String::from("Tools & more"),
+                    icon: Some(String::from("utilities")),
+                    directory: None,
+                    items: vec![MenuItem::Entry(MenuEntry {
+                        id: String::from("calc.desktop"),
+                        label: String::from("Calc (\"new\")"),
+                        icon: None,
+                        exec: Some(String::from("calc %U")),
+                        command: Some(String::from("calc")),
+                        path: PathBuf::from("/usr/share/applications/calc.desktop"),
+                    })],
+                }),
+                MenuItem::Separator,
+            ],
+        };
+        let written = |format: MenuFormat| -> io::Result<String> {
+            let mut out = Vec::new();
+            write_menu(&menu, format, &mut out)?;
+            Ok(String::from_utf8_lossy(&out).into_owned())
+        };
+
+        assert!(written(MenuFormat::Openbox)?.contains(
+            "<menu id=\"root-menu-Utility\" label=\"Tools &amp; more\">\n    \
+            <item label=\"Calc (&quot;new&quot;)\"><action name=\"Execute\">\
+            <command>calc</command></action></item>"
+        ));
+        assert!(written(MenuFormat::Jwm)?
+            .contains("<Program label=\"Calc (&quot;new&quot;)\">calc</Program>"));
+        assert_eq!(
+            written(MenuFormat::Fluxbox)?,
+            "[begin] (Applications)\n  [submenu] (Tools & more)\n    \
+            [exec] (Calc (\"new\"\\)) {calc}\n  [end]\n  [separator]\n[end]\n"
+        );
+        assert_eq!(
+            written(MenuFormat::Icewm)?,
+            "menu \"Tools & more\" utilities {\n    prog \"Calc (\\\"new\\\")\" - calc\n}\n\
+            separator\n"
+        );
+        assert!(written(MenuFormat::Twm)?.contains(
+            "menu \"Applications/Utility\"\n{\n    \"Tools & more\" f.title\n    \
+            \"Calc (\\\"new\\\")\" f.exec \"calc &\"\n}\n"
+        ));
+        Ok(())
+    }
+}
@@ -0,0 +1,603 @@
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use super::create;
+use super::discovery;
+use super::entry::Entry;
+use super::keyfile::KeyFile;
+use super::parallel;
+use super::xdg;
+
+static DEFAULT_APPLICATIONS: &str = "Default Applications";
+static ADDED_ASSOCIATIONS: &str = "Added Associations";
+static REMOVED_ASSOCIATIONS: &str = "Removed Associations";
+static MIME_CACHE: &str = "MIME Cache";
+static MIMEAPPS_LIST: &str = "mimeapps.list";
+static MIMEINFO_CACHE: &str = "mimeinfo.cache";
+
+/// The groups of a key file whose values are lists of desktop file IDs, keyed by MIME type.
+type Lists = HashMap<String, HashMap<String, Vec<String>>>;
+
+/// The associations of one level of precedence: the mimeapps.list files of a directory and, for
+/// an applications directory, the MIME types supported by the entries located in it.
+struct Level {
+    lists: Vec<Lists>,
+    supported: HashMap<String, Vec<String>>,
+}
+
+/// Resolves the applications associated with MIME types as described by the XDG MIME
+/// Applications Specification. The mimeapps.list files of the configuration directories take
+/// precedence over the ones of the applications directories, the desktop-specific files over the
+/// other ones. The MIME types supported by the entries are read from mimeinfo.cache, or from the
+/// MimeType keys of the entries if a directory has no cache.
+pub struct MimeApps {
+    levels: Vec<Level>,
+    installed: HashMap<String, PathBuf>,
+    shown: RefCell<HashMap<String, bool>>,
+}
+
+impl MimeApps {
+    /// Loads the associations using $XDG_CONFIG_HOME, $XDG_CONFIG_DIRS and $XDG_CURRENT_DESKTOP.
+    pub fn load(dirs: &[PathBuf], jobs: usize) -> MimeApps {
+        MimeApps::new(&xdg::config_dirs(), dirs, &xdg::current_desktops(), jobs)
+    }
+
+    /// Loads the associations from the configuration directories and the applications
+    /// directories, both in the order of their precedence.
+    pub fn new(
+        config_dirs: &[PathBuf],
+        dirs: &[PathBuf],
+        desktops: &[String],
+        jobs: usize,
+    ) -> MimeApps {
+        let installed = discovery::get_entries_from_dirs(dirs, jobs);
+
+        let mut levels: Vec<Level> = config_dirs
+            .iter()
+            .map(|dir| Level {
+                lists: read_lists(dir, desktops),
+                supported: HashMap::new(),
+            })
+            .collect();
+        levels.extend(parallel::map(dirs, jobs, |dir| Level {
+            lists: read_lists(dir, desktops),
+            supported: read_supported(dir, &installed),
+        }));
+
+        MimeApps {
+            levels,
+            installed,
+            shown: RefCell::new(HashMap::new()),
+        }
+    }
+
+    /// Returns the ID of the default application for the MIME type, the first installed entry
+    /// listed as default application, or the most preferred associated application.
+    pub fn default_application(&self, mime_type: &str) -> Option<String> {
+        let mime_type = mime_type.to_lowercase();
+        let defaults = self
+            .levels
+            .iter()
+            .flat_map(|level| &level.lists)
+            .flat_map(|lists| values(lists, DEFAULT_APPLICATIONS, &mime_type));
+        for id in defaults {
+            if self.is_installed(id) {
+                return Some(id.clone());
+            }
+        }
+        self.associated_applications(&mime_type).into_iter().next()
+    }
+
+    /// Returns the IDs of the applications associated with the MIME type in the order of their
+    /// preference. Added associations precede the entries supporting the MIME type of the same
+    /// level. Removed associations apply to the added associations of the same and less preferred
+    /// mimeapps.list files, so that a desktop-specific file is not affected by the generic file of
+    /// its directory, and to the entries supporting the MIME type of the same and lower levels.
+    pub fn associated_applications(&self, mime_type: &str) -> Vec<String> {
+        let mime_type = mime_type.to_lowercase();
+        let mut associated: Vec<String> = Vec::new();
+        let mut removed: HashSet<&str> = HashSet::new();
+
+        for level in &self.levels {
+            for lists in &level.lists {
+                removed.extend(values(lists, REMOVED_ASSOCIATIONS, &mime_type).map(String::as_str));
+                for id in values(lists, ADDED_ASSOCIATIONS, &mime_type) {
+                    self.associate(id, &removed, &mut associated);
+                }
+            }
+            for id in level.supported.get(&mime_type).into_iter().flatten() {
+                self.associate(id, &removed, &mut associated);
+            }
+        }
+        associated
+    }
+
+    fn associate(&self, id: &str, removed: &HashSet<&str>, associated: &mut Vec<String>) {
+        if !removed.contains(id)
+            && !associated.iter().any(|other| other == id)
+            && self.is_installed(id)
+        {
+            associated.push(String::from(id));
+        }
+    }
+
+    /// Returns the path of the installed entry with the ID.
+    pub fn path(&self, id: &str) -> Option<&Path> {
+        self.installed.get(id).map(PathBuf::as_path)
+    }
+
+    /// Returns whether the entry is installed and not hidden, which marks it as deleted. Every
+    /// entry is read only once.
+    fn is_installed(&self, id: &str) -> bool {
+        let path = match self.installed.get(id) {
+            Some(path) => path,
+            None => return false,
+        };
+        if let Some(shown) = self.shown.borrow().get(id) {
+            return *shown;
+        }
+        let shown = fs::read_to_string(path)
+            .ok()
+            .and_then(|contents| Entry::new(&contents).ok())
+            .is_some_and(|entry| entry.get("Hidden").is_none_or(|value| value != "true"));
+        self.shown.borrow_mut().insert(String::from(id), shown);
+        shown
+    }
+}
+
+/// Returns the paths of the mimeapps.list files of the directory, the desktop-specific ones first.
+pub fn mimeapps_paths(dir: &Path, desktops: &[String]) -> Vec<PathBuf> {
+    let mut paths: Vec<PathBuf> = desktops
+        .iter()
+        .map(|desktop| dir.join(format!("{}-{}", desktop.to_lowercase(), MIMEAPPS_LIST)))
+        .collect();
+    paths.push(dir.join(MIMEAPPS_LIST));
+    paths
+}
+
+fn read_lists(dir: &Path, desktops: &[String]) -> Vec<Lists> {
+    mimeapps_paths(dir, desktops)
+        .iter()
+        .filter_map(|path| fs::read_to_string(path).ok())
+        .map(|contents| parse_lists(&contents))
+        .collect()
+}
+
+/// Reads the MIME types supported by the entries of the applications directory, ignoring the
+/// entries shadowed by entries of other directories.
+fn read_supported(
+    dir: &Path,
+    installed: &HashMap<String, PathBuf>,
+) -> HashMap<String, Vec<String>> {
+    let mut supported = match fs::read_to_string(dir.join(MIMEINFO_CACHE)) {
+        Ok(contents) => parse_lists(&contents)
+            .remove(MIME_CACHE)
+            .unwrap_or_default(),
+        Err(_) => lowercase_keys(scan_supported(dir)),
+    };
+    for ids in supported.values_mut() {
+        ids.retain(|id| installed.get(id).is_some_and(|path| path.starts_with(dir)));
+    }
+    supported
+}
+
+/// Collects the MIME types supported by the applications of the directory from their MimeType
+/// keys, keeping the MIME types as declared like update-desktop-database does. The entries are
+/// listed in the order of their IDs.
+pub fn scan_supported(dir: &Path) -> HashMap<String, Vec<String>> {
+    let mut entries = HashMap::new();
+    discovery::get_entries_from_path(&discovery::base_path(dir), dir, &mut entries);
+    let mut entries: Vec<(String, PathBuf)> = entries.into_iter().collect();
+    entries.sort();
+
+    let mut supported: HashMap<String, Vec<String>> = HashMap::new();
+    for (id, path) in entries {
+        let entry = match fs::read_to_string(&path)
+            .ok()
+            .and_then(|contents| Entry::new(&contents).ok())
+        {
+            Some(entry) => entry,
+            None => continue,
+        };
+        if entry.get("Type").map(String::as_str) != Some("Application") {
+            continue;
+        }
+        if let Some(mime_types) = entry.get("MimeType") {
+            for mime_type in mime_types.split(';').map(str::trim) {
+                if !mime_type.is_empty() {
+                    supported
+                        .entry(String::from(mime_type))
+                        .or_default()
+                        .push(id.clone());
+                }
+            }
+        }
+    }
+    supported
+}
+
+/// Lowercases the MIME types, as they are case-insensitive, merging the lists of the MIME types
+/// differing only in case.
+fn lowercase_keys(lists: HashMap<String, Vec<String>>) -> HashMap<String, Vec<String>> {
+    let mut lowercased: HashMap<String, Vec<String>> = HashMap::new();
+    for (mime_type, ids) in lists {
+        let merged = lowercased.entry(mime_type.to_lowercase()).or_default();
+        for id in ids {
+            if !merged.contains(&id) {
+                merged.push(id);
+            }
+        }
+    }
+    lowercased
+}
+
+fn values<'a>(lists: &'a Lists, group: &str, mime_type: &str) -> impl Iterator<Item = &'a String> {
+    lists
+        .get(group)
+        .and_then(|group| group.get(mime_type))
+        .into_iter()
+        .flatten()
+}
+
+/// Parses the groups of a key file such as mimeapps.list or mimeinfo.cache, splitting the values
+/// into lists. The keys are lowercased, as MIME types are case-insensitive.
+fn parse_lists(contents: &str) -> Lists {
+    let mut lists: Lists = HashMap::new();
+    let mut group: Option<String> = None;
+
+    for line in contents.lines().map(str::trim) {
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        if line.starts_with('[') && line.ends_with(']') {
+            group = Some(String::from(&line[1..line.len() - 1]));
+            continue;
+        }
+        if let (Some(group), Some((key, value))) = (&group, line.split_once('=')) {
+            let ids = split_list(value);
+            lists
+                .entry(group.clone())
+                .or_default()
+                .insert(key.trim().to_lowercase(), ids);
+        }
+    }
+    lists
+}
+
+fn split_list(value: &str) -> Vec<String> {
+    value
+        .split(';')
+        .map(str::trim)
+        .filter(|id| !id.is_empty())
+        .map(String::from)
+        .collect()
+}
+
+/// Generates the mimeinfo.cache of the applications directory in the format written by
+/// update-desktop-database, listing the MIME types in alphabetical order.
+pub fn generate_cache(dir: &Path) -> String {
+    let supported = scan_supported(dir);
+    let mut mime_types: Vec<&String> = supported.keys().collect();
+    mime_types.sort();
+
+    let mut contents = format!("[{}]\n", MIME_CACHE);
+    for mime_type in mime_types {
+        contents.push_str(&format!(
+            "{}={};\n",
+            mime_type,
+            supported[mime_type].join(";")
+        ));
+    }
+    contents
+}
+
+/// Returns whether the mimeinfo.cache of the applications directory is missing or lists other
+/// entries than the ones supporting the MIME types. The order of the entries and the case of the
+/// MIME types are ignored.
+pub fn is_cache_stale(dir: &Path) -> bool {
+    let contents = match fs::read_to_string(dir.join(MIMEINFO_CACHE)) {
+        Ok(contents) => contents,
+        Err(_) => return true,
+    };
+    let sorted = |mut cache: HashMap<String, Vec<String>>| {
+        for ids in cache.values_mut() {
+            ids.sort();
+        }
+        cache
+    };
+    let cached = parse_lists(&contents)
+        .remove(MIME_CACHE)
+        .unwrap_or_default();
+    sorted(cached) != sorted(lowercase_keys(scan_supported(dir)))
+}
+
+/// Writes the generated mimeinfo.cache into the applications directory. Returns its path.
+pub fn update_cache(dir: &Path) -> io::Result<PathBuf> {
+    let path = dir.join(MIMEINFO_CACHE);
+    create::write_file(&path, &generate_cache(dir))?;
+    Ok(path)
+}
+
+/// Returns the path of the user's mimeapps.list, $XDG_CONFIG_HOME/mimeapps.list.
+pub fn user_mimeapps_path() -> Option<PathBuf> {
+    xdg::config_home().map(|dir| dir.join(MIMEAPPS_LIST))
+}
+
+/// Edits the mimeapps.list file, which is created if it does not exist. The other groups and keys,
+/// comments and the order of the keys are preserved. The file is written only if it changed,
+/// returns whether it did.
+pub fn edit_mimeapps<F>(path: &Path, edit: F) -> io::Result<bool>
+where
+    F: FnOnce(&mut KeyFile),
+{
+    let contents = match fs::read_to_string(path) {
+        Ok(contents) => contents,
+        Err(error) if error.kind() == io::ErrorKind::NotFound => String::new(),
+        Err(error) => return Err(error),
+    };
+    let mut key_file = KeyFile::parse(&contents);
+    let original = key_file.to_string();
+    edit(&mut key_file);
+    let edited = key_file.to_string();
+    if edited == original {
+        return Ok(false);
+    }
+
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    create::write_file(path, &edited)?;
+    Ok(true)
+}
+
+/// Makes the application the default one for the MIME type. It is also moved to the front of the
+/// added associations and removed from the removed associations.
+pub fn set_default(key_file: &mut KeyFile, mime_type: &str, id: &str) {
+    let mime_type = mime_type.to_lowercase();
+    set_list(
+        key_file,
+        DEFAULT_APPLICATIONS,
+        &mime_type,
+        vec![String::from(id)],
+    );
+    let mut added = get_list(key_file, ADDED_ASSOCIATIONS, &mime_type);
+    added.retain(|added_id| added_id != id);
+    added.insert(0, String::from(id));
+    set_list(key_file, ADDED_ASSOCIATIONS, &mime_type, added);
+    remove_from_list(key_file, REMOVED_ASSOCIATIONS, &mime_type, id);
+}
+
+/// Associates the application with the MIME type, appending it to the added associations and
+/// removing it from the removed associations.
+pub fn add_association(key_file: &mut KeyFile, mime_type: &str, id: &str) {
+    let mime_type = mime_type.to_lowercase();
+    let mut added = get_list(key_file, ADDED_ASSOCIATIONS, &mime_type);
+    if !added.iter().any(|added_id| added_id == id) {
+        added.push(String::from(id));
+        set_list(key_file, ADDED_ASSOCIATIONS, &mime_type, added);
+    }
+    remove_from_list(key_file, REMOVED_ASSOCIATIONS, &mime_type, id);
+}
+
+/// Removes the association of the application with the MIME type, removing it from the default
+/// applications and the added associations and appending it to the removed associations.
+pub fn remove_association(key_file: &mut KeyFile, mime_type: &str, id: &str) {
+    let mime_type = mime_type.to_lowercase();
+    remove_from_list(key_file, DEFAULT_APPLICATIONS, &mime_type, id);
+    remove_from_list(key_file, ADDED_ASSOCIATIONS, &mime_type, id);
+    let mut removed = get_list(key_file, REMOVED_ASSOCIATIONS, &mime_type);
+    if !removed.iter().any(|removed_id| removed_id == id) {
+        removed.push(String::from(id));
+        set_list(key_file, REMOVED_ASSOCIATIONS, &mime_type, removed);
+    }
+}
+
+fn get_list(key_file: &KeyFile, group: &str, mime_type: &str) -> Vec<String> {
+    key_file
+        .get(group, mime_type)
+        .map(split_list)
+        .unwrap_or_default()
+}
+
+/// Sets the list, removing the key if the list is empty.
+fn set_list(key_file: &mut KeyFile, group: &str, mime_type: &str, ids: Vec<String>) {
+    if ids.is_empty() {
+        key_file.unset(group, mime_type);
+    } else {
+        key_file.set(group, mime_type, &format!("{};", ids.join(";")));
+    }
+}
+
+fn remove_from_list(key_file: &mut KeyFile, group: &str, mime_type: &str, id: &str) {
+    let mut ids = get_list(key_file, group, mime_type);
+    let len = ids.len();
+    ids.retain(|list_id| list_id != id);
+    if ids.len() != len {
+        set_list(key_file, group, mime_type, ids);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tempdir::TempDir;
+    use std::error::Error;
+
+    fn write_entry(dir: &Path, id: &str, mime_types: &str) -> std::io::Result<()> {
+        fs::create_dir_all(dir)?;
+        fs::write(
+            dir.join(id),
+            format!(
+                "[Desktop Entry]\nType=Application\nName={}\nExec=foo\nMimeType={}\n",
+                id, mime_types
+            ),
+        )
+    }
+
+    #[test]
+    fn test_mime_apps() -> Result<(), Box<dyn Error>> {
+        let root = TempDir::new("mime");
+        let config_dir = root.join("config");
+        let user_dir = root.join("user/applications");
+        let system_dir = root.join("system/applications");
+
+        write_entry(
+            &system_dir,
+            "browser.desktop",
+            "text/html;x-scheme-handler/http;",
+        )?;
+        write_entry(&system_dir, "editor.desktop", "text/plain;text/html;")?;
+        write_entry(&system_dir, "viewer.desktop", "image/png;")?;
+        write_entry(&user_dir, "local.desktop", "text/html;")?;
+        fs::write(
+            system_dir.join(MIMEINFO_CACHE),
+            "[MIME Cache]\ntext/html=editor.desktop;browser.desktop;\n\
+            text/plain=editor.desktop;\nimage/png=viewer.desktop;\n",
+        )?;
+        fs::create_dir_all(&config_dir)?;
+        fs::write(
+            config_dir.join(MIMEAPPS_LIST),
+            "[Added Associations]\nimage/png=editor.desktop;\n\n\
+            [Removed Associations]\ntext/html=local.desktop;\ntext/plain=viewer.desktop;\n",
+        )?;
+        fs::write(
+            config_dir.join("gnome-mimeapps.list"),
+            "[Default Applications]\ntext/plain=missing.desktop;viewer.desktop;\n\n\
+            [Added Associations]\ntext/plain=viewer.desktop;\n",
+        )?;
+        fs::write(
+            system_dir.join(MIMEAPPS_LIST),
+            "[Default Applications]\ntext/html=browser.desktop\n",
+        )?;
+
+        let dirs = vec![user_dir, system_dir];
+        let config_dirs = vec![config_dir];
+        let desktops = vec![String::from("GNOME")];
+        let mime_apps = MimeApps::new(&config_dirs, &dirs, &desktops, 1);
+
+        assert_eq!(
+            mime_apps.default_application("text/html").as_deref(),
+            Some("browser.desktop")
+        );
+        assert_eq!(
+            mime_apps.associated_applications("text/html"),
+            vec!["editor.desktop", "browser.desktop"]
+        );
+        assert_eq!(
+            mime_apps.default_application("TEXT/PLAIN").as_deref(),
+            Some("viewer.desktop")
+        );
+        assert_eq!(
+            mime_apps.associated_applications("image/png"),
+            vec!["editor.desktop", "viewer.desktop"]
+        );
+        assert_eq!(
+            mime_apps.associated_applications("text/plain"),
+            vec!["viewer.desktop", "editor.desktop"]
+        );
+        assert_eq!(mime_apps.default_application("video/mp4"), None);
+
+        let mime_apps = MimeApps::new(&config_dirs, &dirs, &[], 1);
+        assert_eq!(
+            mime_apps.associated_applications("text/plain"),
+            vec!["editor.desktop"]
+        );
+
+        let mime_apps = MimeApps::new(&[], &dirs, &[], 1);
+        assert_eq!(
+            mime_apps.default_application("text/html").as_deref(),
+            Some("browser.desktop")
+        );
+        assert_eq!(
+            mime_apps.default_application("text/plain").as_deref(),
+            Some("editor.desktop")
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_update_cache() -> Result<(), Box<dyn Error>> {
+        let root = TempDir::new("mime-cache");
+        write_entry(&root.join("vendor"), "b.desktop", "text/plain;image/png")?;
+        write_entry(&root, "a.desktop", "text/plain;")?;
+        fs::write(
+            root.join("broken.desktop"),
+            "[Desktop Entry]\ninvalid line\nMimeType=text/plain;\n",
+        )?;
+
+        assert!(is_cache_stale(&root));
+        assert_eq!(
+            generate_cache(&root),
+            "[MIME Cache]\nimage/png=vendor-b.desktop;\ntext/plain=a.desktop;vendor-b.desktop;\n"
+        );
+        update_cache(&root)?;
+        assert!(!is_cache_stale(&root));
+
+        fs::write(
+            root.join(MIMEINFO_CACHE),
+            "[MIME Cache]\ntext/plain=vendor-b.desktop;a.desktop;\nimage/png=vendor-b.desktop;\n",
+        )?;
+        assert!(!is_cache_stale(&root));
+        write_entry(&root, "c.desktop", "image/png;")?;
+        assert!(is_cache_stale(&root));
+        Ok(())
+    }
+
+    #[test]
+    fn test_generate_cache() -> Result<(), Box<dyn Error>> {
+        let root = TempDir::new("mime-generate");
+        write_entry(&root, "a.desktop", "text/plain;application/vnd.MS-Excel;")?;
+        write_entry(&root.join("vendor"), "b.desktop", "text/plain;image/png")?;
+        fs::write(
+            root.join("link.desktop"),
+            "[Desktop Entry]\nType=Link\nName=Link\nURL=https://example.org\nMimeType=text/html;\n",
+        )?;
+        fs::write(
+            root.join("dir.directory"),
+            "[Desktop Entry]\nType=Directory\nName=Dir\nMimeType=text/html;\n",
+        )?;
+
+        // The mimeinfo.cache written by update-desktop-database for the same directory
+        let expected = "[MIME Cache]\n\
+            application/vnd.MS-Excel=a.desktop;\n\
+            image/png=vendor-b.desktop;\n\
+            text/plain=a.desktop;vendor-b.desktop;\n";
+        assert_eq!(generate_cache(&root), expected);
+        fs::write(
+            root.join(MIMEINFO_CACHE),
+            expected.replace("MS-Excel", "ms-excel"),
+        )?;
+        assert!(!is_cache_stale(&root));
+        Ok(())
+    }
+
+    #[test]
+    fn test_edit_associations() {
+        let mut key_file = KeyFile::parse(
+            "# Comment\n[Default Applications]\nimage/png=viewer.desktop;\n\n\
+            [Removed Associations]\ntext/html=firefox.desktop;\n",
+        );
+        set_default(&mut key_file, "text/html", "firefox.desktop");
+        add_association(&mut key_file, "text/plain", "editor.desktop");
+        add_association(&mut key_file, "text/plain", "editor.desktop");
+        remove_association(&mut key_file, "image/png", "viewer.desktop");
+
+        assert_eq!(
+            key_file.to_string(),
+            "# Comment
+[Default Applications]
+text/html=firefox.desktop;
+
+[Removed Associations]
+image/png=viewer.desktop;
+
+[Added Associations]
+text/html=firefox.desktop;
+text/plain=editor.desktop;
+"
+        );
+    }
+}
@@ -0,0 +1,623 @@
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+
+static MAGIC_HEADER: &[u8] = b"MIME-Magic\0\n";
+static TEXT_PLAIN: &str = "text/plain";
+static OCTET_STREAM: &str = "application/octet-stream";
+static DIRECTORY: &str = "inode/directory";
+
+/// The number of bytes read at most for sniffing the contents of a file.
+const MAX_SNIFF_SIZE: usize = 64 * 1024;
+/// The number of bytes checked to tell text from binary data.
+const TEXT_SNIFF_SIZE: usize = 512;
+
+struct Glob {
+    weight: u32,
+    mime_type: String,
+    pattern: String,
+    case_sensitive: bool,
+}
+
+struct MagicMatch {
+    indent: usize,
+    offset: usize,
+    range: usize,
+    value: Vec<u8>,
+    mask: Option<Vec<u8>>,
+}
+
+struct Magic {
+    priority: u32,
+    mime_type: String,
+    matches: Vec<MagicMatch>,
+}
+
+/// The shared-mime-info database, which detects the MIME type of a file by its name using the
+/// globs2 files, or by its contents using the magic files.
+pub struct MimeDatabase {
+    globs: Vec<Glob>,
+    magic: Vec<Magic>,
+    aliases: HashMap<String, String>,
+    parents: HashMap<String, Vec<String>>,
+}
+
+/// Returns the mime directories next to the applications directories.
+pub fn mime_dirs(application_dirs: &[PathBuf]) -> Vec<PathBuf> {
+    application_dirs
+        .iter()
+        .filter_map(|dir| dir.parent())
+        .map(|dir| dir.join("mime"))
+        .collect()
+}
+
+impl MimeDatabase {
+    /// Loads the database from the mime directories in the order of their precedence. Missing
+    /// or invalid files are ignored.
+    pub fn load(dirs: &[PathBuf]) -> MimeDatabase {
+        let mut database = MimeDatabase {
+            globs: Vec::new(),
+            magic: Vec::new(),
+            aliases: HashMap::new(),
+            parents: HashMap::new(),
+        };
+
+        for dir in dirs {
+            if let Ok(contents) = fs::read_to_string(dir.join("globs2")) {
+                database.globs.extend(parse_globs(&contents));
+            }
+            if let Ok(contents) = fs::read(dir.join("magic")) {
+                database.magic.extend(parse_magic(&contents));
+            }
+            if let Ok(contents) = fs::read_to_string(dir.join("aliases")) {
+                for (alias, mime_type) in parse_pairs(&contents) {
+                    database.aliases.entry(alias).or_insert(mime_type);
+                }
+            }
+            if let Ok(contents) = fs::read_to_string(dir.join("subclasses")) {
+                for (mime_type, parent) in parse_pairs(&contents) {
+                    let parents = database.parents.entry(mime_type).or_default();
+                    if !parents.contains(&parent) {
+                        parents.push(parent);
+                    }
+                }
+            }
+        }
+        database.magic.sort_by_key(|magic| std::cmp::Reverse(magic.priority));
+        database
+    }
+
+    /// Detects the MIME type of the file. The globs matching its name are preferred, the magic
+    /// rules are used if no glob matches or to choose between conflicting globs. Files matching
+    /// neither are text/plain if they are empty or look like text, application/octet-stream
+    /// otherwise.
+    pub fn type_of_file(&self, path: &Path) -> io::Result<String> {
+        let metadata = fs::metadata(path)?;
+        if metadata.is_dir() {
+            return Ok(String::from(DIRECTORY));
+        }
+
+        let name = path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let candidates = self.types_by_name(&name);
+        if candidates.len() == 1 {
+            return Ok(String::from(candidates[0]));
+        }
+        if metadata.len() == 0 {
+            return Ok(String::from(
+                candidates.first().copied().unwrap_or(TEXT_PLAIN),
+            ));
+        }
+
+        let data = read_start(path, self.sniff_size())?;
+        let sniffed = self.type_by_data(&data);
+        if let Some(&first) = candidates.first() {
+            let chosen = sniffed.filter(|sniffed| candidates.contains(sniffed));
+            return Ok(String::from(chosen.unwrap_or(first)));
+        }
+        Ok(match sniffed {
+            Some(mime_type) => String::from(mime_type),
+            None if looks_like_text(&data) => String::from(TEXT_PLAIN),
+            None => String::from(OCTET_STREAM),
+        })
+    }
+
+    /// Returns the MIME types whose globs match the file name best, the ones with the highest
+    /// weight and the longest pattern, preferring case-sensitive globs.
+    pub fn types_by_name(&self, name: &str) -> Vec<&str> {
+        let lowercase_name = name.to_lowercase();
+        let mut best: Option<(u32, usize, bool)> = None;
+        let mut types: Vec<&str> = Vec::new();
+
+        for glob in &self.globs {
+            let matched = if glob.case_sensitive {
+                glob_match(&glob.pattern, name)
+            } else {
+                glob_match(&glob.pattern.to_lowercase(), &lowercase_name)
+            };
+            if !matched {
+                continue;
+            }
+
+            let rank = (glob.weight, glob.pattern.len(), glob.case_sensitive);
+            if best.is_none_or(|best| rank > best) {
+                best = Some(rank);
+                types.clear();
+            }
+            if best == Some(rank) && !types.contains(&glob.mime_type.as_str()) {
+                types.push(&glob.mime_type);
+            }
+        }
+        types
+    }
+
+    /// Returns the MIME type of the magic rule with the highest priority matching the data.
+    pub fn type_by_data(&self, data: &[u8]) -> Option<&str> {
+        self.magic
+            .iter()
+            .find(|magic| any_matches(&magic.matches, data))
+            .map(|magic| magic.mime_type.as_str())
+    }
+
+    /// Returns the canonical MIME type of an alias, or the MIME type itself.
+    pub fn unalias<'a>(&'a self, mime_type: &'a str) -> &'a str {
+        self.aliases
+            .get(mime_type)
+            .map_or(mime_type, String::as_str)
+    }
+
+    /// Returns the MIME types the MIME type is a subclass of, nearest first, including the
+    /// implicit text/plain of all text types.
+    pub fn ancestors(&self, mime_type: &str) -> Vec<String> {
+        let mut ancestors: Vec<String> = Vec::new();
+        let mut index = 0;
+        let mut current = String::from(self.unalias(mime_type));
+        loop {
+            let mut parents: Vec<String> = self.parents.get(&current).cloned().unwrap_or_default();
+            if current.starts_with("text/") && current != TEXT_PLAIN {
+                parents.push(String::from(TEXT_PLAIN));
+            }
+            for parent in parents {
+                if parent != mime_type && !ancestors.contains(&parent) {
+                    ancestors.push(parent);
+                }
+            }
+
+            match ancestors.get(index) {
+                Some(next) => current = next.clone(),
+                None => return ancestors,
+            }
+            index += 1;
+        }
+    }
+
+    fn sniff_size(&self) -> usize {
+        self.magic
+            .iter()
+            .flat_map(|magic| &magic.matches)
+            .map(|matched| matched.offset + matched.range + matched.value.len())
+            .max()
+            .unwrap_or(0)
+            .clamp(TEXT_SNIFF_SIZE, MAX_SNIFF_SIZE)
+    }
+}
+
+fn read_start(path: &Path, size: usize) -> io::Result<Vec<u8>> {
+    let mut data = Vec::with_capacity(size);
+    File::open(path)?.take(size as u64).read_to_end(&mut data)?;
+    Ok(data)
+}
+
+/// Data looks like text if its start is valid UTF-8 without control characters other than
+/// whitespace. A multi-byte character cut off at the end is accepted.
+fn looks_like_text(data: &[u8]) -> bool {
+    let data = &data[..data.len().min(TEXT_SNIFF_SIZE)];
+    let valid = match std::str::from_utf8(data) {
+        Ok(text) => text,
+        Err(error) if error.error_len().is_none() => {
+            std::str::from_utf8(&data[..error.valid_up_to()]).unwrap_or_default()
+        }
+        Err(_) => return false,
+    };
+    !valid
+        .chars()
+        .any(|c| c.is_control() && !c.is_whitespace() && c != '\u{1b}')
+}
+
+/// Parses a globs2 file, which consists of lines of the form weight:type:glob[:flags].
+fn parse_globs(contents: &str) -> Vec<Glob> {
+    contents
+        .lines()
+        .filter(|line| !line.starts_with('#'))
+        .filter_map(|line| {
+            let mut fields = line.splitn(4, ':');
+            let weight = fields.next()?.parse().ok()?;
+            let mime_type = fields.next()?;
+            let pattern = fields.next()?;
+            if mime_type == "__NOGLOBS__" {
+                return None;
+            }
+            let case_sensitive = fields
+                .next()
+                .is_some_and(|flags| flags.split(',').any(|flag| flag == "cs"));
+            Some(Glob {
+                weight,
+                mime_type: String::from(mime_type),
+                pattern: String::from(pattern),
+                case_sensitive,
+            })
+        })
+        .collect()
+}
+
+/// Parses files consisting of lines of two MIME types separated by a space, such as aliases and
+/// subclasses.
+fn parse_pairs(contents: &str) -> Vec<(String, String)> {
+    contents
+        .lines()
+        .filter(|line| !line.starts_with('#'))
+        .filter_map(|line| line.split_once(' '))
+        .map(|(first, second)| (String::from(first), String::from(second.trim())))
+        .collect()
+}
+
+/// Parses a magic file. Every section starts with a [priority:type] header followed by lines of
+/// the form [indent]>offset=value[&mask][~word-size][+range], where the value is preceded by its
+/// length as a big-endian 16 bit number. Parsing stops at the first invalid line.
+fn parse_magic(contents: &[u8]) -> Vec<Magic> {
+    let mut sections: Vec<Magic> = Vec::new();
+    let mut parser = Parser {
+        data: contents,
+        position: 0,
+    };
+    if !parser.skip(MAGIC_HEADER) {
+        return sections;
+    }
+
+    while let Some(&byte) = parser.peek() {
+        if byte == b'[' {
+            let header = match parser.until(b'\n') {
+                Some(header) => String::from_utf8_lossy(header).into_owned(),
+                None => break,
+            };
+            let section = header
+                .trim_start_matches('[')
+                .trim_end_matches(']')
+                .split_once(':')
+                .and_then(|(priority, mime_type)| {
+                    Some(Magic {
+                        priority: priority.parse().ok()?,
+                        mime_type: String::from(mime_type),
+                        matches: Vec::new(),
+                    })
+                });
+            match section {
+                Some(section) => sections.push(section),
+                None => break,
+            }
+            continue;
+        }
+
+        match (parser.magic_match(), sections.last_mut()) {
+            (Some(matched), Some(section)) => section.matches.push(matched),
+            (None, _) if parser.until(b'\n').is_some() => {}
+            _ => break,
+        }
+    }
+    sections
+}
+
+struct Parser<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn peek(&self) -> Option<&u8> {
+        self.data.get(self.position)
+    }
+
+    fn skip(&mut self, expected: &[u8]) -> bool {
+        if self.data[self.position..].starts_with(expected) {
+            self.position += expected.len();
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Returns the bytes up to the delimiter and skips the delimiter.
+    fn until(&mut self, delimiter: u8) -> Option<&'a [u8]> {
+        let start = self.position;
+        let length = self.data[start..]
+            .iter()
+            .position(|&byte| byte == delimiter)?;
+        self.position = start + length + 1;
+        Some(&self.data[start..start + length])
+    }
+
+    fn take(&mut self, length: usize) -> Option<&'a [u8]> {
+        let bytes = self.data.get(self.position..self.position + length)?;
+        self.position += length;
+        Some(bytes)
+    }
+
+    fn number(&mut self) -> Option<usize> {
+        let start = self.position;
+        while self.peek().is_some_and(u8::is_ascii_digit) {
+            self.position += 1;
+        }
+        std::str::from_utf8(&self.data[start..self.position])
+            .ok()?
+            .parse()
+            .ok()
+    }
+
+    /// Parses a line of a section. Leaves the position at the start of the line if it cannot be
+    /// parsed.
+    fn magic_match(&mut self) -> Option<MagicMatch> {
+        let start = self.position;
+        let matched = self.parse_magic_match();
+        if matched.is_none() {
+            self.position = start;
+        }
+        matched
+    }
+
+    fn parse_magic_match(&mut self) -> Option<MagicMatch> {
+        let indent = if self.peek() == Some(&b'>') {
+            0
+        } else {
+            self.number()?
+        };
+        if !self.skip(b">") {
+            return None;
+        }
+        let offset = self.number()?;
+        if !self.skip(b"=") {
+            return None;
+        }
+        let length = self.take(2)?;
+        let length = usize::from(u16::from_be_bytes([length[0], length[1]]));
+        let mut value = self.take(length)?.to_vec();
+
+        let mut mask = None;
+        if self.skip(b"&") {
+            mask = Some(self.take(length)?.to_vec());
+        }
+        let mut word_size = 1;
+        if self.skip(b"~") {
+            word_size = self.number()?;
+        }
+        let mut range = 1;
+        if self.skip(b"+") {
+            range = self.number()?;
+        }
+        if !self.skip(b"\n") {
+            return None;
+        }
+
+        if word_size > 1 && cfg!(target_endian = "little") {
+            for chunk in value.chunks_mut(word_size) {
+                chunk.reverse();
+            }
+            if let Some(mask) = &mut mask {
+                for chunk in mask.chunks_mut(word_size) {
+                    chunk.reverse();
+                }
+            }
+        }
+
+        Some(MagicMatch {
+            indent,
+            offset,
+            range: range.max(1),
+            value,
+            mask,
+        })
+    }
+}
+
+impl MagicMatch {
+    fn matches(&self, data: &[u8]) -> bool {
+        (self.offset..self.offset + self.range).any(|offset| {
+            let bytes = match data.get(offset..offset + self.value.len()) {
+                Some(bytes) => bytes,
+                None => return false,
+            };
+            match &self.mask {
+                Some(mask) => bytes
+                    .iter()
+                    .zip(&self.value)
+                    .zip(mask)
+                    .all(|((byte, value), mask)| byte & mask == value & mask),
+                None => bytes == self.value.as_slice(),
+            }
+        })
+    }
+}
+
+/// Returns whether any of the matches of the same indent matches the data along with any of its
+/// nested matches, if it has some.
+fn any_matches(matches: &[MagicMatch], data: &[u8]) -> bool {
+    let indent = match matches.first() {
+        Some(first) => first.indent,
+        None => return false,
+    };
+
+    let mut index = 0;
+    while index < matches.len() {
+        let end = matches[index + 1..]
+            .iter()
+            .position(|matched| matched.indent <= indent)
+            .map_or(matches.len(), |position| index + 1 + position);
+        let nested = &matches[index + 1..end];
+        if matches[index].matches(data) && (nested.is_empty() || any_matches(nested, data)) {
+            return true;
+        }
+        index = end;
+    }
+    false
+}
+
+/// Matches a name against a shell glob supporting *, ? and bracket expressions.
+fn glob_match(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+    glob_match_chars(&pattern, &name)
+}
+
+fn glob_match_chars(pattern: &[char], name: &[char]) -> bool {
+    match pattern.first() {
+        None => name.is_empty(),
+        Some('*') => (0..=name.len()).any(|skip| glob_match_chars(&pattern[1..], &name[skip..])),
+        Some('?') => !name.is_empty() && glob_match_chars(&pattern[1..], &name[1..]),
+        Some('[') => {
+            let c = match name.first() {
+                Some(&c) => c,
+                None => return false,
+            };
+            match bracket_match(&pattern[1..], c) {
+                Some((matched, length)) => {
+                    matched && glob_match_chars(&pattern[1 + length..], &name[1..])
+                }
+                None => name.first() == Some(&'[') && glob_match_chars(&pattern[1..], &name[1..]),
+            }
+        }
+        Some(&p) => name.first() == Some(&p) && glob_match_chars(&pattern[1..], &name[1..]),
+    }
+}
+
+/// Matches a character against the bracket expression following the opening bracket. Returns
+/// whether it matched and the length of the expression including the closing bracket, or none if
+/// the expression is not closed.
+fn bracket_match(pattern: &[char], c: char) -> Option<(bool, usize)> {
+    let negated = pattern.first() == Some(&'!');
+    let mut index = usize::from(negated);
+    let mut matched = false;
+    let mut first = true;
+
+    while let Some(&p) = pattern.get(index) {
+        if p == ']' && !first {
+            return Some((matched != negated, index + 1));
+        }
+        first = false;
+        if pattern.get(index + 1) == Some(&'-') && pattern.get(index + 2).is_some_and(|&e| e != ']')
+        {
+            let end = pattern[index + 2];
+            matched |= p <= c && c <= end;
+            index += 3;
+        } else {
+            matched |= p == c;
+            index += 1;
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tempdir::TempDir;
+
+    fn magic_file() -> Vec<u8> {
+        let mut magic = MAGIC_HEADER.to_vec();
+        magic.extend(b"[50:image/png]\n>0=\0\x04\x89PNG\n");
+        magic.extend(b"[80:application/x-foo]\n>0=\0\x03FOO\n1>4=\0\x01A\n1>4=\0\x01B&\xff\n");
+        magic.extend(b"[40:application/x-word]\n>8=\0\x02\x12\x34~2+4\n");
+        magic
+    }
+
+    #[test]
+    fn test_globs() {
+        let contents = "# comment
+50:text/x-csrc:*.c:cs
+50:text/x-c++src:*.C:cs
+50:text/x-csrc:*.c
+60:text/x-readme:README*
+50:application/x-compressed-tar:*.tar.gz
+40:application/gzip:*.gz
+50:text/x-a:*.[ab]
+50:text/x-b:*.[ab]
+";
+        let database = MimeDatabase {
+            globs: parse_globs(contents),
+            magic: Vec::new(),
+            aliases: HashMap::new(),
+            parents: HashMap::new(),
+        };
+
+        assert_eq!(database.types_by_name("main.c"), vec!["text/x-csrc"]);
+        assert_eq!(database.types_by_name("main.C"), vec!["text/x-c++src"]);
+        assert_eq!(database.types_by_name("README.md"), vec!["text/x-readme"]);
+        assert_eq!(
+            database.types_by_name("a.tar.gz"),
+            vec!["application/x-compressed-tar"]
+        );
+        assert_eq!(database.types_by_name("a.gz"), vec!["application/gzip"]);
+        assert_eq!(database.types_by_name("x.b"), vec!["text/x-a", "text/x-b"]);
+        assert!(database.types_by_name("a.txt").is_empty());
+
+        assert!(glob_match("[!a-c]x", "dx"));
+        assert!(!glob_match("[!a-c]x", "bx"));
+        assert!(glob_match("*.[Jj][Pp][Gg]", "photo.JpG"));
+    }
+
+    #[test]
+    fn test_magic() {
+        let magic = parse_magic(&magic_file());
+        assert_eq!(magic.len(), 3);
+        assert_eq!(magic[1].matches.len(), 3);
+        assert_eq!(magic[1].matches[1].indent, 1);
+        assert_eq!(magic[2].matches[0].range, 4);
+
+        let mut database = MimeDatabase {
+            globs: Vec::new(),
+            magic,
+            aliases: HashMap::new(),
+            parents: HashMap::new(),
+        };
+        database.magic.sort_by_key(|magic| std::cmp::Reverse(magic.priority));
+
+        assert_eq!(database.type_by_data(b"\x89PNG\r\n"), Some("image/png"));
+        assert_eq!(database.type_by_data(b"FOO B"), Some("application/x-foo"));
+        assert_eq!(database.type_by_data(b"FOO C"), None);
+        assert_eq!(
+            database.type_by_data(b"\0\0\0\0\0\0\0\0\0\0\x34\x12"),
+            Some("application/x-word")
+        );
+    }
+
+    #[test]
+    fn test_type_of_file() -> io::Result<()> {
+        let root = TempDir::new("mimedb");
+        let mime_dir = root.join("mime");
+        fs::create_dir_all(&mime_dir)?;
+        fs::write(mime_dir.join("globs2"), "50:image/png:*.png\n")?;
+        fs::write(mime_dir.join("magic"), magic_file())?;
+        fs::write(mime_dir.join("aliases"), "image/x-png image/png\n")?;
+        fs::write(mime_dir.join("subclasses"), "text/x-csrc text/x-c\n")?;
+        let database = MimeDatabase::load(&mime_dirs(&[root.join("applications")]));
+
+        fs::write(root.join("image.png"), "not really")?;
+        fs::write(root.join("image"), b"\x89PNG\r\n")?;
+        fs::write(root.join("notes"), "Some text\n")?;
+        fs::write(root.join("binary"), b"\0\x01\x02")?;
+        assert_eq!(database.type_of_file(&root.join("image.png"))?, "image/png");
+        assert_eq!(database.type_of_file(&root.join("image"))?, "image/png");
+        assert_eq!(database.type_of_file(&root.join("notes"))?, TEXT_PLAIN);
+        assert_eq!(database.type_of_file(&root.join("binary"))?, OCTET_STREAM);
+        assert_eq!(database.type_of_file(&root)?, DIRECTORY);
+
+        assert_eq!(database.unalias("image/x-png"), "image/png");
+        assert_eq!(
+            database.ancestors("text/x-csrc"),
+            vec!["text/x-c", TEXT_PLAIN]
+        );
+
+        Ok(())
+    }
+}
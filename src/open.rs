@@ -0,0 +1,253 @@
+use core::fmt;
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use super::entry::Entry;
+use super::exec;
+use super::mime::MimeApps;
+use super::mimedb::{self, MimeDatabase};
+
+static FILE_URL_PREFIX: &str = "file://";
+
+/// What to open, a local file or a URL opened by the handler of its scheme.
+#[derive(Debug, PartialEq)]
+pub enum Target {
+    File(PathBuf),
+    Url { url: String, scheme: String },
+}
+
+impl Target {
+    /// Parses the argument. Existing paths and file URLs are files, other arguments starting
+    /// with a scheme are URLs.
+    pub fn parse(argument: &str) -> Target {
+        if let Some(path) = argument.strip_prefix(FILE_URL_PREFIX) {
+            let path = path.split(['?', '#']).next().unwrap_or_default();
+            let path = path.find('/').map_or("", |start| &path[start..]);
+            return Target::File(PathBuf::from(percent_decode(path)));
+        }
+        if Path::new(argument).exists() {
+            return Target::File(PathBuf::from(argument));
+        }
+
+        match url_scheme(argument) {
+            Some(scheme) => Target::Url {
+                url: String::from(argument),
+                scheme: scheme.to_lowercase(),
+            },
+            None => Target::File(PathBuf::from(argument)),
+        }
+    }
+
+    /// Returns the MIME type of the file, or x-scheme-handler/scheme for URLs.
+    pub fn mime_type(&self, database: &MimeDatabase) -> Result<String, OpenError> {
+        match self {
+            Target::File(path) => database.type_of_file(path).map_err(|error| {
+                OpenError::new(format!("Could not open {}: {}", path.display(), error))
+            }),
+            Target::Url { scheme, .. } => Ok(format!("x-scheme-handler/{}", scheme)),
+        }
+    }
+
+    /// Returns the argument passed to the application.
+    fn argument(&self) -> String {
+        match self {
+            Target::File(path) => path.display().to_string(),
+            Target::Url { url, .. } => url.clone(),
+        }
+    }
+}
+
+/// Returns the default application for the MIME type, or for the nearest type it is a subclass
+/// of if no application handles the type itself.
+pub fn find_application(
+    mime_type: &str,
+    database: &MimeDatabase,
+    mime_apps: &MimeApps,
+) -> Option<String> {
+    let mime_type = database.unalias(mime_type);
+    std::iter::once(String::from(mime_type))
+        .chain(database.ancestors(mime_type))
+        .find_map(|mime_type| mime_apps.default_application(&mime_type))
+}
+
+/// Returns the applications associated with the MIME type followed by the ones associated with
+/// the types it is a subclass of, nearest first.
+pub fn find_applications(
+    mime_type: &str,
+    database: &MimeDatabase,
+    mime_apps: &MimeApps,
+) -> Vec<String> {
+    let mime_type = database.unalias(mime_type);
+    let mut applications: Vec<String> = Vec::new();
+    for mime_type in std::iter::once(String::from(mime_type)).chain(database.ancestors(mime_type)) {
+        for id in mime_apps.associated_applications(&mime_type) {
+            if !applications.contains(&id) {
+                applications.push(id);
+            }
+        }
+    }
+    applications
+}
+
+/// Opens the file or URL with the default application for its MIME type, expanding the Exec key
+/// of the application's entry with the file or URL. The language strings localize the name passed
+/// by the %c field code. Returns the ID of the application.
+pub fn open(
+    argument: &str,
+    dirs: &[PathBuf],
+    language_strings: &[String],
+    jobs: usize,
+) -> Result<String, OpenError> {
+    let target = Target::parse(argument);
+    let database = MimeDatabase::load(&mimedb::mime_dirs(dirs));
+    let mime_type = target.mime_type(&database)?;
+    let mime_apps = MimeApps::load(dirs, jobs);
+
+    let id = find_application(&mime_type, &database, &mime_apps)
+        .ok_or_else(|| OpenError::new(format!("No application found for {}", mime_type)))?;
+    let path = mime_apps
+        .path(&id)
+        .ok_or_else(|| OpenError::new(format!("Could not find entry {}", id)))?;
+    let entry = fs::read_to_string(path)
+        .map_err(|error| error.to_string())
+        .and_then(|contents| Entry::new(&contents).map_err(|error| error.to_string()))
+        .map_err(|error| OpenError::new(format!("Could not read {}: {}", path.display(), error)))?;
+
+    exec::launch(&entry, path, &[target.argument()], language_strings)
+        .map_err(|error| OpenError::new(format!("{} in {}", error, path.display())))?;
+    Ok(id)
+}
+
+/// Returns the scheme of a URL, which starts with a letter followed by letters, digits, +, - and
+/// dots. Single letters are not treated as schemes.
+fn url_scheme(argument: &str) -> Option<&str> {
+    let (scheme, _) = argument.split_once(':')?;
+    let mut chars = scheme.chars();
+    let valid = chars.next().is_some_and(|c| c.is_ascii_alphabetic())
+        && chars.all(|c| c.is_ascii_alphanumeric() || "+-.".contains(c))
+        && scheme.len() > 1;
+    if valid {
+        Some(scheme)
+    } else {
+        None
+    }
+}
+
+/// Decodes the %XX escapes of a URL path, leaving invalid escapes as they are.
+fn percent_decode(value: &str) -> String {
+    let bytes = value.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut index = 0;
+    while index < bytes.len() {
+        let escaped = bytes
+            .get(index + 1..index + 3)
+            .and_then(|hex| u8::from_str_radix(std::str::from_utf8(hex).ok()?, 16).ok());
+        match escaped {
+            Some(byte) if bytes[index] == b'%' => {
+                decoded.push(byte);
+                index += 3;
+            }
+            _ => {
+                decoded.push(bytes[index]);
+                index += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+#[derive(Debug)]
+pub struct OpenError {
+    message: String,
+}
+
+impl OpenError {
+    fn new(message: String) -> OpenError {
+        OpenError { message }
+    }
+}
+
+impl Error for OpenError {}
+
+impl Display for OpenError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tempdir::TempDir;
+
+    #[test]
+    fn test_parse_target() {
+        assert_eq!(
+            Target::parse("https://example.com/a?b"),
+            Target::Url {
+                url: String::from("https://example.com/a?b"),
+                scheme: String::from("https"),
+            }
+        );
+        assert_eq!(
+            Target::parse("MAILTO:someone@example.com"),
+            Target::Url {
+                url: String::from("MAILTO:someone@example.com"),
+                scheme: String::from("mailto"),
+            }
+        );
+        assert_eq!(
+            Target::parse("file:///tmp/a%20b.txt"),
+            Target::File(PathBuf::from("/tmp/a b.txt"))
+        );
+        assert_eq!(
+            Target::parse("file://localhost/tmp/c%2"),
+            Target::File(PathBuf::from("/tmp/c%2"))
+        );
+        assert_eq!(
+            Target::parse("notes.txt"),
+            Target::File(PathBuf::from("notes.txt"))
+        );
+        assert_eq!(
+            Target::parse("c:notes.txt"),
+            Target::File(PathBuf::from("c:notes.txt"))
+        );
+    }
+
+    #[test]
+    fn test_find_application() -> Result<(), Box<dyn Error>> {
+        let root = TempDir::new("open");
+        let applications = root.join("applications");
+        let mime_dir = root.join("mime");
+        fs::create_dir_all(&applications)?;
+        fs::create_dir_all(&mime_dir)?;
+        fs::write(
+            applications.join("editor.desktop"),
+            "[Desktop Entry]\nType=Application\nName=Editor\nExec=editor %F\n\
+            MimeType=text/plain;\n",
+        )?;
+        fs::write(mime_dir.join("globs2"), "50:text/x-csrc:*.c\n")?;
+        fs::write(mime_dir.join("aliases"), "text/x-c text/x-csrc\n")?;
+
+        let dirs = vec![applications];
+        let database = MimeDatabase::load(&mimedb::mime_dirs(&dirs));
+        let mime_apps = MimeApps::new(&[], &dirs, &[], 1);
+        fs::write(root.join("main.c"), "int main;\n")?;
+        let target = Target::parse(&root.join("main.c").display().to_string());
+
+        let mime_type = target.mime_type(&database)?;
+        assert_eq!(mime_type, "text/x-csrc");
+        assert_eq!(
+            find_application("text/x-c", &database, &mime_apps).as_deref(),
+            Some("editor.desktop")
+        );
+        assert_eq!(find_application("image/png", &database, &mime_apps), None);
+        assert_eq!(
+            find_applications("text/x-c", &database, &mime_apps),
+            vec!["editor.desktop"]
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,233 @@
+use serde::{Deserialize, Serialize};
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use super::entry::Entry;
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct MatchedEntry {
+    pub id: String,
+    pub path: PathBuf,
+    pub contents: String,
+    pub entry: Entry,
+    pub score: Option<i64>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Format {
+    Entries,
+    Menu,
+    Dmenu,
+    Rofi,
+    Tsv,
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "entries" => Ok(Format::Entries),
+            "menu" => Ok(Format::Menu),
+            "dmenu" => Ok(Format::Dmenu),
+            "rofi" => Ok(Format::Rofi),
+            "tsv" => Ok(Format::Tsv),
+            _ => Err(format!("Unknown output format {}", s)),
+        }
+    }
+}
+
+pub struct Printer<'a> {
+    format: Format,
+    language_strings: &'a [String],
+    with_generic_name: bool,
+}
+
+impl<'a> Printer<'a> {
+    pub fn new(
+        format: Format,
+        language_strings: &'a [String],
+        with_generic_name: bool,
+    ) -> Printer<'a> {
+        Printer {
+            format,
+            language_strings,
+            with_generic_name,
+        }
+    }
+
+    pub fn label(&self, matched: &MatchedEntry) -> String {
+        let name = matched
+            .entry
+            .get_localized("Name", self.language_strings)
+            .unwrap_or(&matched.id);
+        let mut label = name.clone();
+
+        if self.with_generic_name {
+            if let Some(generic_name) = matched
+                .entry
+                .get_localized("GenericName", self.language_strings)
+            {
+                if !generic_name.is_empty() && generic_name != name {
+                    label = format!("{} ({})", label, generic_name);
+                }
+            }
+        }
+
+        label.replace(['\t', '\n', '\r'], " ")
+    }
+
+    pub fn write(&self, out: &mut impl Write, matched: &MatchedEntry) -> io::Result<()> {
+        match self.format {
+            Format::Entries => {
+                writeln!(out, "{}", matched.path.display())?;
+                writeln!(out, "{}", matched.contents)
+            }
+            Format::Menu => writeln!(out, "{}\t{}", self.label(matched), matched.id),
+            Format::Dmenu => writeln!(out, "{}", self.label(matched)),
+            Format::Rofi => match matched.entry.get("Icon") {
+                Some(icon) => writeln!(out, "{}\0icon\x1f{}", self.label(matched), icon),
+                None => writeln!(out, "{}", self.label(matched)),
+            },
+            Format::Tsv => {
+                let score = matched
+                    .score
+                    .map(|score| score.to_string())
+                    .unwrap_or_default();
+                writeln!(
+                    out,
+                    "{}\t{}\t{}\t{}",
+                    escape_tsv(&matched.id),
+                    escape_tsv(&matched.path.to_string_lossy()),
+                    escape_tsv(&self.label(matched)),
+                    score
+                )
+            }
+        }
+    }
+
+    /// Finds the entry a line printed by one of the menu formats refers to. The ID column is
+    /// preferred, with the label used as a fallback for launchers which only return the label.
+    pub fn find<'m>(&self, line: &str, matches: &'m [MatchedEntry]) -> Option<&'m MatchedEntry> {
+        let line = line.trim_end_matches(['\n', '\r']);
+        let line = match line.find('\0') {
+            Some(index) => &line[..index],
+            None => line,
+        };
+
+        let id = match line.rfind('\t') {
+            Some(index) => &line[index + 1..],
+            None => line,
+        };
+        if let Some(matched) = matches.iter().find(|matched| matched.id == id) {
+            return Some(matched);
+        }
+
+        let mut found = matches.iter().filter(|matched| self.label(matched) == line);
+        match (found.next(), found.next()) {
+            (Some(matched), None) => Some(matched),
+            _ => None,
+        }
+    }
+}
+
+/// Escapes backslashes, tabs and line breaks, so that every value stays in its own column and
+/// every entry on its own line.
+fn escape_tsv(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '\\' => escaped.push_str("\\\\"),
+            '\t' => escaped.push_str("\\t"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn create_match(id: &str, name: &str, generic_name: &str) -> MatchedEntry {
+        let mut entries = HashMap::new();
+        entries.insert(String::from("Name"), String::from(name));
+        entries.insert(String::from("GenericName"), String::from(generic_name));
+        entries.insert(String::from("Icon"), String::from("icon"));
+        MatchedEntry {
+            id: String::from(id),
+            path: PathBuf::from(id),
+            contents: String::new(),
+            entry: Entry::from_entries(entries),
+            score: None,
+        }
+    }
+
+    #[test]
+    fn test_menu_lines() {
+        let matched = create_match("foo.desktop", "Foo", "Browser");
+        let mut out = Vec::new();
+
+        Printer::new(Format::Menu, &[], true)
+            .write(&mut out, &matched)
+            .unwrap();
+        Printer::new(Format::Dmenu, &[], true)
+            .write(&mut out, &matched)
+            .unwrap();
+        Printer::new(Format::Rofi, &[], false)
+            .write(&mut out, &matched)
+            .unwrap();
+        Printer::new(Format::Tsv, &[], false)
+            .write(&mut out, &matched)
+            .unwrap();
+
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "Foo (Browser)\tfoo.desktop\nFoo (Browser)\nFoo\0icon\x1ficon\n\
+            foo.desktop\tfoo.desktop\tFoo\t\n"
+        );
+    }
+
+    #[test]
+    fn test_tsv_escaping() {
+        let mut matched = create_match("foo.desktop", "Foo\nBar", "Browser");
+        matched.path = PathBuf::from("/tmp/a\tb\\c/foo.desktop");
+        matched.score = Some(3);
+        let mut out = Vec::new();
+
+        Printer::new(Format::Tsv, &[], false)
+            .write(&mut out, &matched)
+            .unwrap();
+
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "foo.desktop\t/tmp/a\\tb\\\\c/foo.desktop\tFoo Bar\t3\n"
+        );
+    }
+
+    #[test]
+    fn test_find() {
+        let matches = vec![
+            create_match("foo.desktop", "Foo", "Browser"),
+            create_match("bar.desktop", "Bar", "Browser"),
+            create_match("baz.desktop", "Bar", "Editor"),
+        ];
+        let printer = Printer::new(Format::Dmenu, &[], true);
+
+        assert_eq!(
+            printer.find("Foo (Browser)\n", &matches).unwrap().id,
+            "foo.desktop"
+        );
+        assert_eq!(
+            printer.find("Bar\tbaz.desktop", &matches).unwrap().id,
+            "baz.desktop"
+        );
+        assert!(Printer::new(Format::Dmenu, &[], false)
+            .find("Bar", &matches)
+            .is_none());
+    }
+}
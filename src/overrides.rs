@@ -0,0 +1,310 @@
+use core::fmt;
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use super::create;
+use super::discovery;
+use super::entry::Entry;
+use super::exec;
+use super::keyfile::KeyFile;
+use super::validate;
+use super::xdg;
+
+/// Returns the user's applications directory, $XDG_DATA_HOME/applications.
+pub fn user_applications_dir() -> Option<PathBuf> {
+    xdg::data_home().map(|dir| dir.join("applications"))
+}
+
+/// Appends the .desktop suffix to an ID specified without it.
+pub fn normalize_id(id: &str) -> String {
+    if id.ends_with(".desktop") {
+        String::from(id)
+    } else {
+        format!("{}.desktop", id)
+    }
+}
+
+/// Edits the entry with the ID, which is found in the directories the same way as the queries
+/// find it. An entry located in the user's directory is edited in place, any other entry is
+/// copied into the user's directory first, so that the copy overrides it. The edited entry is
+/// validated before it is written, nothing is written if the edit function reports that it did not
+/// change anything. Returns the path of the edited file.
+pub fn edit<F>(
+    id: &str,
+    dirs: &[PathBuf],
+    user_dir: &Path,
+    jobs: usize,
+    edit: F,
+) -> Result<PathBuf, EditError>
+where
+    F: FnOnce(&mut KeyFile) -> bool,
+{
+    let entries = discovery::get_entries_from_dirs(dirs, jobs);
+    let source = match entries.get(id) {
+        Some(source) => source,
+        None => return Err(EditError::new(format!("Could not find entry {}", id))),
+    };
+
+    let target = if source.starts_with(user_dir) {
+        source.clone()
+    } else {
+        let target = user_dir.join(id);
+        if target.exists() {
+            return Err(EditError::new(format!(
+                "{} is shadowed by {}, refusing to replace it",
+                target.display(),
+                source.display()
+            )));
+        }
+        target
+    };
+
+    let contents = fs::read_to_string(source).map_err(|error| {
+        EditError::new(format!("Could not read {}: {}", source.display(), error))
+    })?;
+    let mut key_file = KeyFile::parse(&contents);
+    if !edit(&mut key_file) {
+        return Err(EditError::new(format!(
+            "Nothing to change in {}",
+            source.display()
+        )));
+    }
+    let contents = key_file.to_string();
+
+    let problems = validate::validate(&contents);
+    if !problems.is_empty() {
+        return Err(EditError::new(format!(
+            "The edited entry is invalid: {}",
+            problems.join(", ")
+        )));
+    }
+    for warning in validate::warnings(&contents) {
+        eprintln!("Warning: {} in {}", warning, target.display());
+    }
+
+    write(&target, &contents).map_err(|error| {
+        EditError::new(format!("Could not write {}: {}", target.display(), error))
+    })?;
+    Ok(target)
+}
+
+/// Removes the user's override of the entry with the ID if it has the same keys, comments and
+/// order as the entry it overrides, so that reverting all edits leaves no copy behind. Returns the
+/// path of the overridden entry, which is in effect again, if the override was removed.
+pub fn remove_redundant(
+    id: &str,
+    dirs: &[PathBuf],
+    user_dir: &Path,
+    jobs: usize,
+) -> io::Result<Option<PathBuf>> {
+    let user_path = user_dir.join(id);
+    if !user_path.is_file() {
+        return Ok(None);
+    }
+    let other_dirs: Vec<PathBuf> = dirs
+        .iter()
+        .filter(|dir| !dir.starts_with(user_dir))
+        .cloned()
+        .collect();
+    let overridden = match discovery::get_entries_from_dirs(&other_dirs, jobs).remove(id) {
+        Some(path) => path,
+        None => return Ok(None),
+    };
+
+    let user_contents = KeyFile::parse(&fs::read_to_string(&user_path)?);
+    let overridden_contents = KeyFile::parse(&fs::read_to_string(&overridden)?);
+    if user_contents.to_string() != overridden_contents.to_string() {
+        return Ok(None);
+    }
+    fs::remove_file(&user_path)?;
+    Ok(Some(overridden))
+}
+
+/// Removes the entry with the ID from the user's directory. Entries installed in other
+/// directories are never removed. Returns the path of the removed file.
+pub fn remove(
+    id: &str,
+    dirs: &[PathBuf],
+    user_dir: &Path,
+    jobs: usize,
+) -> Result<PathBuf, EditError> {
+    let user_entries = discovery::get_entries_from_dirs(&[user_dir.to_path_buf()], jobs);
+    let path = match user_entries.get(id) {
+        Some(path) => path,
+        None => {
+            return Err(match discovery::get_entries_from_dirs(dirs, jobs).get(id) {
+                Some(path) => EditError::new(format!(
+                    "{} is installed in {}, only entries in {} can be removed",
+                    id,
+                    path.display(),
+                    user_dir.display()
+                )),
+                None => EditError::new(format!("Could not find entry {}", id)),
+            })
+        }
+    };
+
+    fs::remove_file(path).map_err(|error| {
+        EditError::new(format!("Could not remove {}: {}", path.display(), error))
+    })?;
+    Ok(path.clone())
+}
+
+/// An application in the user's directory whose program no longer exists.
+pub struct StaleEntry {
+    pub id: String,
+    pub path: PathBuf,
+    pub program: String,
+}
+
+/// Finds the applications in the user's directory whose TryExec or Exec program cannot be found,
+/// sorted by ID. Entries which cannot be parsed are skipped.
+pub fn stale_entries(user_dir: &Path, jobs: usize) -> Vec<StaleEntry> {
+    let entries = discovery::get_entries_from_dirs(&[user_dir.to_path_buf()], jobs);
+    let mut stale: Vec<StaleEntry> = entries
+        .into_iter()
+        .filter_map(|(id, path)| {
+            let contents = discovery::read_entry(&path).ok()?;
+            let entry = Entry::new(&contents).ok()?;
+            if entry.get("Type").map(String::as_str) != Some("Application") {
+                return None;
+            }
+            let program = exec::missing_program(&entry)?;
+            Some(StaleEntry { id, path, program })
+        })
+        .collect();
+    stale.sort_by(|a, b| a.id.cmp(&b.id));
+    stale
+}
+
+fn write(path: &Path, contents: &str) -> io::Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    create::write_file(path, contents)
+}
+
+#[derive(Debug)]
+pub struct EditError {
+    message: String,
+}
+
+impl EditError {
+    fn new(message: String) -> EditError {
+        EditError { message }
+    }
+}
+
+impl Error for EditError {}
+
+impl Display for EditError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::keyfile::DESKTOP_ENTRY_GROUP;
+    use crate::tempdir::TempDir;
+
+    #[test]
+    fn test_edit() -> Result<(), Box<dyn Error>> {
+        let root = TempDir::new("overrides");
+        let user_dir = root.join("user/applications");
+        let system_dir = root.join("system/applications");
+        fs::create_dir_all(system_dir.join("vendor"))?;
+        let system_path = system_dir.join("vendor/foo.desktop");
+        let contents = "[Desktop Entry]\n# Comment\nType=Application\nName=Foo\nExec=foo\n";
+        fs::write(&system_path, contents)?;
+        let dirs = vec![user_dir.clone(), system_dir];
+
+        let path = edit("vendor-foo.desktop", &dirs, &user_dir, 1, |key_file| {
+            key_file.set(DESKTOP_ENTRY_GROUP, "Exec", "env FOO=1 foo");
+            true
+        })?;
+        assert_eq!(path, user_dir.join("vendor-foo.desktop"));
+        assert_eq!(
+            fs::read_to_string(&path)?,
+            "[Desktop Entry]\n# Comment\nType=Application\nName=Foo\nExec=env FOO=1 foo\n"
+        );
+        assert_eq!(fs::read_to_string(&system_path)?, contents);
+
+        let path = edit("vendor-foo.desktop", &dirs, &user_dir, 1, |key_file| {
+            key_file.set(DESKTOP_ENTRY_GROUP, "NoDisplay", "true");
+            true
+        })?;
+        assert!(fs::read_to_string(&path)?.ends_with("Exec=env FOO=1 foo\nNoDisplay=true\n"));
+
+        let invalid = edit("vendor-foo.desktop", &dirs, &user_dir, 1, |key_file| {
+            key_file.unset(DESKTOP_ENTRY_GROUP, "Name")
+        });
+        assert!(invalid.is_err());
+        assert!(edit("missing.desktop", &dirs, &user_dir, 1, |_| true).is_err());
+        let unchanged = edit("vendor-foo.desktop", &dirs, &user_dir, 1, |key_file| {
+            key_file.unset(DESKTOP_ENTRY_GROUP, "Icon")
+        });
+        assert!(unchanged.is_err());
+
+        assert_eq!(
+            remove_redundant("vendor-foo.desktop", &dirs, &user_dir, 1)?,
+            None
+        );
+        edit("vendor-foo.desktop", &dirs, &user_dir, 1, |key_file| {
+            key_file.set(DESKTOP_ENTRY_GROUP, "Exec", "foo");
+            key_file.unset(DESKTOP_ENTRY_GROUP, "NoDisplay")
+        })?;
+        assert_eq!(
+            remove_redundant("vendor-foo.desktop", &dirs, &user_dir, 1)?,
+            Some(system_path)
+        );
+        assert!(!path.exists());
+        Ok(())
+    }
+
+    #[test]
+    fn test_remove() -> Result<(), Box<dyn Error>> {
+        let root = TempDir::new("remove");
+        let user_dir = root.join("user/applications");
+        let system_dir = root.join("system/applications");
+        fs::create_dir_all(&user_dir)?;
+        fs::create_dir_all(&system_dir)?;
+        let contents = [
+            (
+                "foo.desktop",
+                "[Desktop Entry]\nType=Application\nName=Foo\nExec=sh\n",
+            ),
+            (
+                "stale.desktop",
+                "[Desktop Entry]\nType=Application\nName=Stale\nExec=/nonexistent/stale %U\n",
+            ),
+            (
+                "link.desktop",
+                "[Desktop Entry]\nType=Link\nName=Link\nURL=https://example.com\n",
+            ),
+        ];
+        for (id, contents) in &contents {
+            fs::write(user_dir.join(id), contents)?;
+        }
+        fs::write(system_dir.join("system.desktop"), contents[0].1)?;
+        let dirs = vec![user_dir.clone(), system_dir];
+
+        let stale = stale_entries(&user_dir, 1);
+        assert_eq!(stale.len(), 1);
+        assert_eq!(stale[0].id, "stale.desktop");
+        assert_eq!(stale[0].program, "/nonexistent/stale");
+
+        assert_eq!(
+            remove("stale.desktop", &dirs, &user_dir, 1)?,
+            user_dir.join("stale.desktop")
+        );
+        assert!(stale_entries(&user_dir, 1).is_empty());
+        assert!(remove("system.desktop", &dirs, &user_dir, 1).is_err());
+        assert!(remove("stale.desktop", &dirs, &user_dir, 1).is_err());
+        Ok(())
+    }
+}
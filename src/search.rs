@@ -0,0 +1,295 @@
+use std::path::Path;
+
+use super::exec;
+use super::history::{self, History};
+use super::output::MatchedEntry;
+use super::sort::{self, SortKey};
+
+static NAME_WEIGHT: i64 = 10;
+static GENERIC_NAME_WEIGHT: i64 = 6;
+static KEYWORDS_WEIGHT: i64 = 6;
+static EXEC_WEIGHT: i64 = 5;
+static ID_WEIGHT: i64 = 4;
+static COMMENT_WEIGHT: i64 = 2;
+
+static MATCH_SCORE: i64 = 16;
+static START_BONUS: i64 = 24;
+static WORD_BONUS: i64 = 16;
+static CONSECUTIVE_BONUS: i64 = 16;
+static EXACT_BONUS: i64 = 64;
+static GAP_PENALTY: i64 = 1;
+static MAX_LEADING_PENALTY: i64 = 12;
+
+/// A fuzzy search over the localized Name, GenericName, Keywords and Comment keys, the basename of
+/// the executable and the ID of the entries. Every field is scored separately and weighted by its
+/// relevance, the best weighted score becomes the score of the entry.
+pub struct Search<'a> {
+    pattern: Vec<char>,
+    language_strings: &'a [String],
+}
+
+impl<'a> Search<'a> {
+    pub fn new(term: &str, language_strings: &'a [String]) -> Search<'a> {
+        Search {
+            pattern: term
+                .chars()
+                .filter(|c| !c.is_whitespace())
+                .flat_map(char::to_lowercase)
+                .collect(),
+            language_strings,
+        }
+    }
+
+    pub fn score(&self, matched: &MatchedEntry) -> Option<i64> {
+        let entry = &matched.entry;
+        let mut best = None;
+
+        let mut consider = |text: Option<&str>, weight: i64| {
+            if let Some(score) = text.and_then(|text| self.score_text(text)) {
+                best = best.max(Some(score * weight));
+            }
+        };
+
+        consider(
+            entry
+                .get_localized("Name", self.language_strings)
+                .map(String::as_str),
+            NAME_WEIGHT,
+        );
+        consider(
+            entry
+                .get_localized("GenericName", self.language_strings)
+                .map(String::as_str),
+            GENERIC_NAME_WEIGHT,
+        );
+        if let Some(keywords) = entry.get_localized("Keywords", self.language_strings) {
+            for keyword in keywords.split(';') {
+                consider(Some(keyword), KEYWORDS_WEIGHT);
+            }
+        }
+        consider(
+            entry
+                .get_localized("Comment", self.language_strings)
+                .map(String::as_str),
+            COMMENT_WEIGHT,
+        );
+        let executable = entry.get("Exec").and_then(|value| executable_name(value));
+        consider(executable.as_deref(), EXEC_WEIGHT);
+        consider(Some(matched.id.trim_end_matches(".desktop")), ID_WEIGHT);
+
+        best
+    }
+
+    /// Scores how well the pattern matches the text. All characters of the pattern have to occur
+    /// in the text in the same order. Matches at the start of the text or of its words and
+    /// consecutive matches are preferred, gaps between the matches are penalized.
+    fn score_text(&self, text: &str) -> Option<i64> {
+        if self.pattern.is_empty() {
+            return Some(0);
+        }
+
+        // The text is folded the same way as the pattern, a character may fold to several ones,
+        // which get the bonus of the original character only once.
+        let original: Vec<char> = text.chars().collect();
+        let mut text = Vec::with_capacity(original.len());
+        let mut bonuses = Vec::with_capacity(original.len());
+        for (index, c) in original.iter().enumerate() {
+            let bonus = position_bonus(&original, index);
+            for (fold_index, folded) in c.to_lowercase().enumerate() {
+                text.push(folded);
+                bonuses.push(if fold_index == 0 { bonus } else { 0 });
+            }
+        }
+        if text.len() < self.pattern.len() {
+            return None;
+        }
+
+        // scores[j] is the best score of the pattern prefix matched so far ending at text[j]
+        let mut scores: Vec<Option<i64>> = text
+            .iter()
+            .enumerate()
+            .map(|(j, &c)| {
+                if c == self.pattern[0] {
+                    let leading_penalty = (j as i64 * GAP_PENALTY).min(MAX_LEADING_PENALTY);
+                    Some(MATCH_SCORE + bonuses[j] - leading_penalty)
+                } else {
+                    None
+                }
+            })
+            .collect();
+
+        for &pattern_char in &self.pattern[1..] {
+            let mut next = vec![None; text.len()];
+            let mut gap_best: Option<i64> = None;
+
+            for j in 1..text.len() {
+                if j >= 2 {
+                    gap_best = gap_best
+                        .map(|score| score - GAP_PENALTY)
+                        .max(scores[j - 2].map(|score| score - GAP_PENALTY));
+                }
+                if text[j] != pattern_char {
+                    continue;
+                }
+
+                let consecutive = scores[j - 1].map(|score| score + CONSECUTIVE_BONUS);
+                if let Some(previous) = consecutive.max(gap_best) {
+                    next[j] = Some(previous + MATCH_SCORE + bonuses[j]);
+                }
+            }
+            scores = next;
+        }
+
+        let best = scores.into_iter().flatten().max()?;
+        if text == self.pattern {
+            Some(best + EXACT_BONUS)
+        } else {
+            Some(best)
+        }
+    }
+}
+
+/// How the entries are searched, sorted and limited. The collation locale is a POSIX locale used
+/// to compare the values when sorting by the name or another key.
+pub struct Ranking<'a> {
+    pub search_term: Option<&'a str>,
+    pub sort_key: Option<&'a SortKey>,
+    pub reverse: bool,
+    pub limit: Option<usize>,
+    pub collation_locale: &'a str,
+}
+
+/// Keeps only the entries matching the search term, boosted by the launch history, then sorts and
+/// limits the entries. The entries are sorted by their scores when searching and by their IDs
+/// otherwise, unless a sort key is specified.
+pub fn rank_entries(
+    matches: &mut Vec<MatchedEntry>,
+    ranking: &Ranking,
+    language_strings: &[String],
+    history: &History,
+) {
+    if let Some(term) = ranking.search_term {
+        let search = Search::new(term, language_strings);
+        let now = history::now();
+        for matched in matches.iter_mut() {
+            matched.score = search
+                .score(matched)
+                .map(|score| score + history.search_bonus(&matched.id, now));
+        }
+        matches.retain(|matched| matched.score.is_some());
+    }
+
+    let default_key = match ranking.search_term {
+        Some(_) => SortKey::Score,
+        None => SortKey::Id,
+    };
+    sort::sort_entries(
+        matches,
+        ranking.sort_key.unwrap_or(&default_key),
+        language_strings,
+        ranking.collation_locale,
+        history,
+        ranking.reverse,
+    );
+    if let Some(limit) = ranking.limit {
+        matches.truncate(limit);
+    }
+}
+
+fn position_bonus(text: &[char], index: usize) -> i64 {
+    if index == 0 {
+        return START_BONUS;
+    }
+
+    let previous = text[index - 1];
+    let current = text[index];
+    if !previous.is_alphanumeric() || (previous.is_lowercase() && current.is_uppercase()) {
+        WORD_BONUS
+    } else {
+        0
+    }
+}
+
+/// Returns the basename of the executable started by an Exec value, skipping env invocations.
+fn executable_name(exec: &str) -> Option<String> {
+    exec::program(exec).map(|program| {
+        Path::new(&program)
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or(program)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::entry::Entry;
+    use std::collections::HashMap;
+    use std::path::PathBuf;
+
+    fn create_match(id: &str, keys: &[(&str, &str)]) -> MatchedEntry {
+        let mut entries = HashMap::new();
+        for (key, value) in keys {
+            entries.insert(String::from(*key), String::from(*value));
+        }
+        MatchedEntry {
+            id: String::from(id),
+            path: PathBuf::from(id),
+            contents: String::new(),
+            entry: Entry::from_entries(entries),
+            score: None,
+        }
+    }
+
+    #[test]
+    fn test_score_text() {
+        let search = Search::new("ff", &[]);
+
+        let word_starts = search.score_text("Firefox").unwrap();
+        let inner = search.score_text("Diff").unwrap();
+        assert!(word_starts > inner);
+        assert!(search.score_text("Files").is_none());
+
+        let search = Search::new("term", &[]);
+        assert!(search.score_text("term").unwrap() > search.score_text("Terminal").unwrap());
+        assert!(search.score_text("Terminal").unwrap() > search.score_text("The Remote").unwrap());
+
+        // U+0130 folds to two characters, in the pattern as well as in the text
+        let search = Search::new("\u{130}st", &[]);
+        assert!(search.score_text("\u{130}stanbul").is_some());
+        assert!(search.score_text("I\u{307}STANBUL").is_some());
+    }
+
+    #[test]
+    fn test_score_fields() {
+        let language_strings = vec![String::from("[de]")];
+        let search = Search::new("browser", &language_strings);
+
+        let by_name = create_match("a.desktop", &[("Name", "Browser")]);
+        let by_generic_name = create_match(
+            "b.desktop",
+            &[("Name", "Firefox"), ("GenericName", "Web Browser")],
+        );
+        let by_comment = create_match(
+            "c.desktop",
+            &[("Name", "Foo"), ("Comment[de]", "Ein Browser")],
+        );
+        let no_match = create_match("d.desktop", &[("Name", "Editor")]);
+
+        let name_score = search.score(&by_name).unwrap();
+        let generic_name_score = search.score(&by_generic_name).unwrap();
+        let comment_score = search.score(&by_comment).unwrap();
+        assert!(name_score > generic_name_score);
+        assert!(generic_name_score > comment_score);
+        assert_eq!(search.score(&no_match), None);
+    }
+
+    #[test]
+    fn test_executable_name() {
+        assert_eq!(
+            executable_name("env FOO=bar /usr/bin/firefox %u"),
+            Some(String::from("firefox"))
+        );
+        assert_eq!(executable_name("vim"), Some(String::from("vim")));
+    }
+}
@@ -0,0 +1,252 @@
+use icu_collator::{Collator, CollatorOptions};
+use icu_provider::DataLocale;
+use std::cmp::{Ordering, Reverse};
+use std::env;
+use std::fs;
+use std::str::FromStr;
+use std::time::SystemTime;
+
+use super::history::{self, History};
+use super::output::MatchedEntry;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SortKey {
+    Id,
+    Name,
+    Path,
+    Mtime,
+    Score,
+    Frecency,
+    Key(String),
+}
+
+impl FromStr for SortKey {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "" => Err(String::from("Sort key cannot be empty")),
+            "id" => Ok(SortKey::Id),
+            "name" => Ok(SortKey::Name),
+            "path" => Ok(SortKey::Path),
+            "mtime" => Ok(SortKey::Mtime),
+            "score" => Ok(SortKey::Score),
+            "frecency" => Ok(SortKey::Frecency),
+            key => Ok(SortKey::Key(String::from(key))),
+        }
+    }
+}
+
+/// Sorts the matched entries by the specified key, collating the values according to the POSIX
+/// locale. Entries with equal keys are ordered by their IDs, entries missing the key are placed
+/// last.
+pub fn sort_entries(
+    matches: &mut [MatchedEntry],
+    sort_key: &SortKey,
+    language_strings: &[String],
+    collation_locale: &str,
+    history: &History,
+    reverse: bool,
+) {
+    match sort_key {
+        SortKey::Id => matches.sort_by(|a, b| a.id.cmp(&b.id)),
+        SortKey::Path => matches.sort_by(|a, b| a.path.cmp(&b.path).then(a.id.cmp(&b.id))),
+        SortKey::Mtime => {
+            matches.sort_by_cached_key(|matched| {
+                let modified = fs::metadata(&matched.path).and_then(|metadata| metadata.modified());
+                (
+                    modified.unwrap_or(SystemTime::UNIX_EPOCH),
+                    matched.id.clone(),
+                )
+            });
+        }
+        SortKey::Score => matches.sort_by(|a, b| b.score.cmp(&a.score).then(a.id.cmp(&b.id))),
+        SortKey::Frecency => {
+            let now = history::now();
+            matches.sort_by_cached_key(|matched| {
+                (
+                    Reverse(history.frecency(&matched.id, now)),
+                    matched.id.clone(),
+                )
+            });
+        }
+        SortKey::Name => {
+            let collator = collator(collation_locale);
+            sort_by_value(matches, "Name", language_strings, collator.as_ref());
+        }
+        SortKey::Key(key) => {
+            let collator = collator(collation_locale);
+            sort_by_value(matches, key, language_strings, collator.as_ref());
+        }
+    }
+
+    if reverse {
+        matches.reverse();
+    }
+}
+
+fn sort_by_value(
+    matches: &mut [MatchedEntry],
+    key: &str,
+    language_strings: &[String],
+    collator: Option<&Collator>,
+) {
+    // The values are looked up once, as looking up a localized value formats the localized keys.
+    let values: Vec<Option<&String>> = matches
+        .iter()
+        .map(|matched| matched.entry.get_localized(key, language_strings))
+        .collect();
+    let mut order: Vec<usize> = (0..matches.len()).collect();
+    order.sort_by(|&a, &b| {
+        let ordering = match (values[a], values[b]) {
+            (Some(a_value), Some(b_value)) => collate(collator, a_value, b_value),
+            (a_value, b_value) => a_value.is_none().cmp(&b_value.is_none()),
+        };
+        ordering.then_with(|| matches[a].id.cmp(&matches[b].id))
+    });
+    permute(matches, &order);
+}
+
+/// Moves the items so that the item at the index order[i] is placed at the index i.
+fn permute<T>(items: &mut [T], order: &[usize]) {
+    for (index, &source) in order.iter().enumerate() {
+        // Items before the index were swapped away already, follow them to their new place.
+        let mut source = source;
+        while source < index {
+            source = order[source];
+        }
+        items.swap(index, source);
+    }
+}
+
+/// Returns the locale used for collation, taken from $LC_ALL, $LC_COLLATE or $LANG in this order
+/// like the C library does.
+pub fn collation_locale() -> String {
+    ["LC_ALL", "LC_COLLATE", "LANG"]
+        .iter()
+        .filter_map(|name| env::var(name).ok())
+        .find(|value| !value.is_empty())
+        .unwrap_or_default()
+}
+
+/// Creates a collator for a POSIX locale such as "de_DE.UTF-8@euro", which orders strings by
+/// their base letters first, so that f.e. "apple" is placed between "Alpha" and "Banana" and
+/// "Émacs" before "Zoom", using the accents and the case only to order otherwise equal strings.
+/// Locales without collation data use the root collation. Returns none for the C and POSIX
+/// locales, which order strings by their code points.
+fn collator(locale: &str) -> Option<Collator> {
+    let language = locale.split(['.', '@']).next().unwrap_or_default();
+    let locale: DataLocale = match language {
+        "" | "C" | "POSIX" => return None,
+        language => language.replace('_', "-").parse().unwrap_or_default(),
+    };
+    Collator::try_new(&locale, CollatorOptions::new())
+        .or_else(|_| Collator::try_new(&DataLocale::default(), CollatorOptions::new()))
+        .ok()
+}
+
+fn collate(collator: Option<&Collator>, a: &str, b: &str) -> Ordering {
+    match collator {
+        Some(collator) => collator.compare(a, b),
+        None => a.cmp(b),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::entry::Entry;
+    use std::collections::HashMap;
+    use std::path::{Path, PathBuf};
+
+    fn create_match(id: &str, name: Option<&str>) -> MatchedEntry {
+        let mut entries = HashMap::new();
+        if let Some(name) = name {
+            entries.insert(String::from("Name"), String::from(name));
+        }
+        MatchedEntry {
+            id: String::from(id),
+            path: PathBuf::from(format!("/usr/share/applications/{}", id)),
+            contents: String::new(),
+            entry: Entry::from_entries(entries),
+            score: None,
+        }
+    }
+
+    fn empty_history() -> History {
+        History::load(Path::new("/nonexistent/desktopentries/history"))
+    }
+
+    fn ids(matches: &[MatchedEntry]) -> Vec<&str> {
+        matches.iter().map(|matched| matched.id.as_str()).collect()
+    }
+
+    #[test]
+    fn test_sort_by_name() {
+        let mut matches = vec![
+            create_match("a.desktop", Some("Banana")),
+            create_match("b.desktop", None),
+            create_match("c.desktop", Some("apple")),
+            create_match("d.desktop", Some("Alpha")),
+        ];
+
+        let collator = collator("en_US.UTF-8");
+        sort_by_value(&mut matches, "Name", &[], collator.as_ref());
+        assert_eq!(
+            ids(&matches),
+            vec!["d.desktop", "c.desktop", "a.desktop", "b.desktop"]
+        );
+
+        sort_by_value(&mut matches, "Name", &[], None);
+        assert_eq!(
+            ids(&matches),
+            vec!["d.desktop", "a.desktop", "c.desktop", "b.desktop"]
+        );
+
+        sort_entries(&mut matches, &SortKey::Id, &[], "", &empty_history(), true);
+        assert_eq!(
+            ids(&matches),
+            vec!["d.desktop", "c.desktop", "b.desktop", "a.desktop"]
+        );
+    }
+
+    #[test]
+    fn test_collator() {
+        let sorted = |locale: &str, names: &[&'static str]| {
+            let collator = collator(locale);
+            let mut names = names.to_vec();
+            names.sort_by(|a, b| collate(collator.as_ref(), a, b));
+            names
+        };
+
+        let names = [
+            "Zoom", "émacs", "Émacs", "Emacs", "Écrire", "Ecrire", "apple",
+        ];
+        assert_eq!(
+            sorted("fr_FR.UTF-8", &names),
+            ["apple", "Ecrire", "Écrire", "Emacs", "émacs", "Émacs", "Zoom"]
+        );
+        assert_eq!(
+            sorted("C.UTF-8", &names),
+            ["Ecrire", "Emacs", "Zoom", "apple", "Écrire", "Émacs", "émacs"]
+        );
+        assert_eq!(
+            sorted("sv_SE.UTF-8", &["öl", "Zoom", "Ol"]),
+            ["Ol", "Zoom", "öl"]
+        );
+        assert_eq!(
+            sorted("invalid locale", &["Zoom", "Émacs"]),
+            ["Émacs", "Zoom"]
+        );
+    }
+
+    #[test]
+    fn test_parse_sort_key() {
+        assert_eq!("mtime".parse(), Ok(SortKey::Mtime));
+        assert_eq!(
+            "X-Custom".parse(),
+            Ok(SortKey::Key(String::from("X-Custom")))
+        );
+        assert!("".parse::<SortKey>().is_err());
+    }
+}
@@ -0,0 +1,301 @@
+use regex::Regex;
+use std::collections::HashSet;
+use std::sync::OnceLock;
+
+use super::categories::{self, MAIN_CATEGORIES};
+use super::entry::Entry;
+use super::exec;
+
+static TYPES: &[&str] = &["Application", "Link", "Directory"];
+static BOOLEAN_KEYS: &[&str] = &[
+    "NoDisplay",
+    "Hidden",
+    "DBusActivatable",
+    "Terminal",
+    "StartupNotify",
+    "PrefersNonDefaultGPU",
+    "SingleMainWindow",
+];
+static FIELD_CODES: &[char] = &['f', 'F', 'u', 'U', 'i', 'c', 'k', '%'];
+static DEPRECATED_FIELD_CODES: &[char] = &['d', 'D', 'n', 'N', 'v', 'm'];
+
+/// Parses the entry the same way as the queries do, then checks it against the rules of the
+/// Desktop Entry Specification which the parser does not enforce. Returns the problems found,
+/// which are empty if the entry is valid.
+pub fn validate(contents: &str) -> Vec<String> {
+    let mut problems = Vec::new();
+
+    let first_line = contents
+        .lines()
+        .map(str::trim)
+        .find(|line| !line.is_empty() && !line.starts_with('#'));
+    if first_line != Some("[Desktop Entry]") {
+        problems.push(String::from("The first group is not [Desktop Entry]"));
+    }
+
+    let entry = match Entry::new(contents) {
+        Ok(entry) => entry,
+        Err(error) => {
+            problems.push(error.to_string());
+            return problems;
+        }
+    };
+    problems.extend(check_keys(contents));
+
+    let entry_type = entry.get("Type").map(String::as_str);
+    match entry_type {
+        None => problems.push(String::from("Missing required key Type")),
+        Some(value) if !TYPES.contains(&value) => problems.push(format!("Unknown Type {}", value)),
+        _ => {}
+    }
+    if entry.get("Name").is_none() {
+        problems.push(String::from("Missing required key Name"));
+    }
+
+    if let Some(version) = entry.get("Version") {
+        let valid = version
+            .split_once('.')
+            .is_some_and(|(major, minor)| is_number(major) && is_number(minor));
+        if !valid {
+            problems.push(format!("Invalid Version {}", version));
+        }
+    }
+    for key in BOOLEAN_KEYS {
+        if let Some(value) = entry.get(key) {
+            if value != "true" && value != "false" {
+                problems.push(format!("Value of {} is not a boolean: {}", key, value));
+            }
+        }
+    }
+
+    match entry_type {
+        Some("Application") => {
+            let dbus_activatable = entry
+                .get("DBusActivatable")
+                .is_some_and(|value| value == "true");
+            if entry.get("Exec").is_none() && !dbus_activatable {
+                problems.push(String::from("Missing required key Exec"));
+            }
+            if entry.get("URL").is_some() {
+                problems.push(String::from("URL is only valid in entries of Type Link"));
+            }
+        }
+        Some("Link") if entry.get("URL").is_none() => {
+            problems.push(String::from("Missing required key URL"));
+        }
+        _ => {}
+    }
+    if let Some(value) = entry.get("Exec") {
+        problems.extend(check_exec(value));
+    }
+
+    problems
+}
+
+/// Checks the entry for problems which do not make it invalid, but which menus may not handle
+/// as intended. Returns no warnings if the entry cannot be parsed.
+pub fn warnings(contents: &str) -> Vec<String> {
+    match Entry::new(contents) {
+        Ok(entry) => entry
+            .get("Categories")
+            .map(|value| check_categories(value))
+            .unwrap_or_default(),
+        Err(_) => Vec::new(),
+    }
+}
+
+/// Checks the Categories value against the registered categories of the Desktop Menu
+/// Specification. Reports categories which are neither registered nor start with X-, additional
+/// categories without their related categories and values without a main category.
+pub fn check_categories(value: &str) -> Vec<String> {
+    let values = categories::parse(value);
+    let mut problems = Vec::new();
+
+    for category in &values {
+        if category.starts_with("X-") || categories::is_registered(category) {
+            continue;
+        }
+        match categories::suggestion(category) {
+            Some(suggestion) => problems.push(format!(
+                "Unknown category {}, did you mean {}?",
+                category, suggestion
+            )),
+            None => problems.push(format!(
+                "Unknown category {}, categories which are not registered should start with X-",
+                category
+            )),
+        }
+    }
+
+    for category in &values {
+        let related = categories::related_categories(category).unwrap_or_default();
+        let satisfied = related.is_empty()
+            || related
+                .iter()
+                .any(|alternative| alternative.iter().all(|name| values.contains(name)));
+        if !satisfied {
+            let alternatives: Vec<String> = related
+                .iter()
+                .map(|alternative| alternative.join(";"))
+                .collect();
+            problems.push(format!(
+                "Category {} requires {}",
+                category,
+                alternatives.join(" or ")
+            ));
+        }
+    }
+
+    if !values.is_empty()
+        && !values
+            .iter()
+            .any(|category| MAIN_CATEGORIES.contains(category))
+    {
+        problems.push(String::from(
+            "Categories does not contain any registered main category",
+        ));
+    }
+    problems
+}
+
+/// Checks the names of the keys of the [Desktop Entry] group and reports duplicate keys, which
+/// the parser silently overrides.
+fn check_keys(contents: &str) -> Vec<String> {
+    static KEY_REGEX: OnceLock<Regex> = OnceLock::new();
+    let key_regex = KEY_REGEX.get_or_init(|| {
+        Regex::new(r"^[A-Za-z0-9-]+(\[[A-Za-z]+(_[A-Za-z]+)?(\.[A-Za-z0-9-]+)?(@[A-Za-z]+)?\])?$")
+            .unwrap()
+    });
+    let mut problems = Vec::new();
+    let mut keys = HashSet::new();
+
+    for line in contents.lines() {
+        if line == "[Desktop Entry]" || line.starts_with('#') || line.trim() == "" {
+            continue;
+        } else if line.starts_with('[') {
+            break;
+        }
+        if let Some((key, _)) = line.split_once('=') {
+            let key = key.trim();
+            if !key_regex.is_match(key) {
+                problems.push(format!("Invalid key {}", key));
+            }
+            if !keys.insert(key) {
+                problems.push(format!("Duplicate key {}", key));
+            }
+        }
+    }
+    problems
+}
+
+fn check_exec(value: &str) -> Vec<String> {
+    let arguments = match exec::split_exec(value) {
+        Ok(arguments) => arguments,
+        Err(error) => return vec![error.to_string()],
+    };
+
+    let mut problems = Vec::new();
+    let mut file_codes = 0;
+    for argument in &arguments {
+        let mut chars = argument.chars();
+        while let Some(c) = chars.next() {
+            if c != '%' {
+                continue;
+            }
+            match chars.next() {
+                Some(code) if FIELD_CODES.contains(&code) => {
+                    if "fFuU".contains(code) {
+                        file_codes += 1;
+                    }
+                }
+                Some(code) if DEPRECATED_FIELD_CODES.contains(&code) => {}
+                Some(code) => problems.push(format!("Invalid field code %{} in Exec", code)),
+                None => problems.push(String::from("Incomplete field code % in Exec")),
+            }
+        }
+    }
+    if file_codes > 1 {
+        problems.push(String::from(
+            "Exec contains more than one of the %f, %F, %u and %U field codes",
+        ));
+    }
+    problems
+}
+
+fn is_number(value: &str) -> bool {
+    !value.is_empty() && value.chars().all(|c| c.is_ascii_digit())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_valid_entry() {
+        let contents = "[Desktop Entry]
+Type=Application
+Version=1.5
+Name=Foo
+Name[de_DE@euro]=Fuu
+Exec=/opt/foo/bin/foo %U
+Terminal=false
+
+[Desktop Action new]
+Name=New";
+        assert!(validate(contents).is_empty());
+    }
+
+    #[test]
+    fn test_invalid_entry() {
+        let contents = "Type=Link
+Name=Foo
+Name=Bar
+Bad_Key=value
+Terminal=yes
+Exec=foo %x %f %U";
+        assert_eq!(
+            validate(contents),
+            vec![
+                "The first group is not [Desktop Entry]",
+                "Duplicate key Name",
+                "Invalid key Bad_Key",
+                "Value of Terminal is not a boolean: yes",
+                "Missing required key URL",
+                "Invalid field code %x in Exec",
+                "Exec contains more than one of the %f, %F, %u and %U field codes",
+            ]
+        );
+
+        let problems = validate("[Desktop Entry]\ninvalid line");
+        assert_eq!(problems.len(), 1);
+        assert!(problems[0].contains("Could not extract value"));
+    }
+
+    #[test]
+    fn test_check_categories() {
+        assert!(check_categories("Development;IDE;X-Custom;").is_empty());
+        assert!(check_categories("AudioVideo;Audio;Player;Midi;").is_empty());
+        assert!(check_categories("").is_empty());
+        assert_eq!(
+            check_categories("Qt;"),
+            vec!["Categories does not contain any registered main category"]
+        );
+        assert_eq!(
+            check_categories("Utilty;Audio;Printing;Settings;Foo;"),
+            vec![
+                "Unknown category Utilty, did you mean Utility?",
+                "Unknown category Foo, categories which are not registered should start with X-",
+                "Category Audio requires AudioVideo",
+                "Category Printing requires HardwareSettings;Settings",
+            ]
+        );
+        assert_eq!(
+            check_categories("Network;Player;"),
+            vec!["Category Player requires Audio or Video or AudioVideo"]
+        );
+        assert_eq!(
+            warnings("[Desktop Entry]\nCategories=KDE;Utility;\n"),
+            vec!["Category KDE requires Qt"]
+        );
+    }
+}
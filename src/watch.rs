@@ -0,0 +1,228 @@
+use inotify::{Event, EventMask, Inotify, WatchMask};
+use std::collections::HashMap;
+use std::ffi::OsStr;
+use std::fmt::{self, Display, Formatter};
+use std::io;
+use std::path::PathBuf;
+use std::thread;
+use std::time::Duration;
+
+use super::discovery::{self, EntryKind};
+use super::output::MatchedEntry;
+
+static EVENT_BUFFER_SIZE: usize = 4096;
+
+/// The kind of a change of the matched entries.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Change {
+    Added,
+    Changed,
+    Removed,
+}
+
+impl Display for Change {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Change::Added => write!(f, "added"),
+            Change::Changed => write!(f, "changed"),
+            Change::Removed => write!(f, "removed"),
+        }
+    }
+}
+
+/// Watches the applications directories and all of their subdirectories using inotify. A
+/// directory which does not exist yet is watched through its nearest existing ancestor, so that
+/// its creation is noticed as well.
+pub struct Watcher {
+    inotify: Inotify,
+    dirs: Vec<PathBuf>,
+    buffer: Vec<u8>,
+}
+
+impl Watcher {
+    pub fn new(dirs: &[PathBuf]) -> io::Result<Watcher> {
+        let mut watcher = Watcher {
+            inotify: Inotify::init()?,
+            dirs: dirs.to_vec(),
+            buffer: vec![0; EVENT_BUFFER_SIZE],
+        };
+        watcher.add_watches();
+        Ok(watcher)
+    }
+
+    /// Blocks until an entry or a directory changes, then waits until nothing changed for the
+    /// debounce duration, so that a package manager installing many files causes a single
+    /// rescan. New subdirectories are watched before returning.
+    pub fn wait(&mut self, debounce: Duration) -> io::Result<()> {
+        loop {
+            let events = self.inotify.read_events_blocking(&mut self.buffer)?;
+            if events.into_iter().any(|event| is_relevant(&event)) {
+                break;
+            }
+        }
+
+        loop {
+            thread::sleep(debounce);
+            let mut changed = false;
+            loop {
+                let mut events = self.inotify.read_events(&mut self.buffer)?.peekable();
+                if events.peek().is_none() {
+                    break;
+                }
+                if events.any(|event| is_relevant(&event)) {
+                    changed = true;
+                }
+            }
+            if !changed {
+                break;
+            }
+        }
+
+        self.add_watches();
+        Ok(())
+    }
+
+    /// Adds the watches for the directories and their subdirectories. Adding a watch for an
+    /// already watched directory does not create a new one, watches of removed directories are
+    /// removed by the kernel.
+    fn add_watches(&mut self) {
+        let mask = WatchMask::CREATE
+            | WatchMask::DELETE
+            | WatchMask::MODIFY
+            | WatchMask::CLOSE_WRITE
+            | WatchMask::MOVE
+            | WatchMask::DELETE_SELF
+            | WatchMask::MOVE_SELF;
+
+        for dir in &self.dirs {
+            let mut watched_dirs = Vec::new();
+            if dir.is_dir() {
+                discovery::scan_path(
+                    &discovery::base_path(dir),
+                    dir,
+                    EntryKind::Desktop,
+                    &mut HashMap::new(),
+                    &mut watched_dirs,
+                );
+            } else if let Some(ancestor) = dir.ancestors().find(|ancestor| ancestor.is_dir()) {
+                watched_dirs.push(ancestor.to_path_buf());
+            }
+
+            for watched_dir in watched_dirs {
+                if let Err(error) = self.inotify.add_watch(&watched_dir, mask) {
+                    eprintln!("Could not watch {}: {}", watched_dir.display(), error);
+                }
+            }
+        }
+    }
+}
+
+/// Returns whether the event may change the entries, ignoring files other than entries such as
+/// the caches written by update-desktop-database.
+fn is_relevant(event: &Event<&OsStr>) -> bool {
+    if event.mask.intersects(
+        EventMask::ISDIR | EventMask::DELETE_SELF | EventMask::MOVE_SELF | EventMask::Q_OVERFLOW,
+    ) {
+        return true;
+    }
+    event.name.is_some_and(|name| {
+        let name = name.to_string_lossy();
+        name.ends_with(".desktop") || name.ends_with(".directory")
+    })
+}
+
+/// Compares the matched entries before and after a rescan by their IDs. An entry whose path or
+/// contents differ is changed, which also covers an entry becoming shadowed by another one with
+/// the same ID. The added and changed entries keep the order of the new entries, followed by the
+/// removed ones in the order of the old entries.
+pub fn diff<'a>(
+    old: &'a [MatchedEntry],
+    new: &'a [MatchedEntry],
+) -> Vec<(Change, &'a MatchedEntry)> {
+    let old_by_id: HashMap<&str, &MatchedEntry> = old
+        .iter()
+        .map(|matched| (matched.id.as_str(), matched))
+        .collect();
+    let new_by_id: HashMap<&str, &MatchedEntry> = new
+        .iter()
+        .map(|matched| (matched.id.as_str(), matched))
+        .collect();
+
+    let mut changes = Vec::new();
+    for matched in new {
+        match old_by_id.get(matched.id.as_str()) {
+            None => changes.push((Change::Added, matched)),
+            Some(old_matched) => {
+                if old_matched.path != matched.path || old_matched.contents != matched.contents {
+                    changes.push((Change::Changed, matched));
+                }
+            }
+        }
+    }
+    for matched in old {
+        if !new_by_id.contains_key(matched.id.as_str()) {
+            changes.push((Change::Removed, matched));
+        }
+    }
+    changes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::entry::Entry;
+    use crate::tempdir::TempDir;
+    use std::fs;
+
+    fn create_match(id: &str, path: &str, contents: &str) -> MatchedEntry {
+        MatchedEntry {
+            id: String::from(id),
+            path: PathBuf::from(path),
+            contents: String::from(contents),
+            entry: Entry::new(contents).unwrap(),
+            score: None,
+        }
+    }
+
+    #[test]
+    fn test_diff() {
+        let old = vec![
+            create_match("a.desktop", "/first/a.desktop", "Name=A"),
+            create_match("b.desktop", "/second/b.desktop", "Name=B"),
+            create_match("c.desktop", "/first/c.desktop", "Name=C"),
+        ];
+        let new = vec![
+            create_match("a.desktop", "/first/a.desktop", "Name=A"),
+            create_match("b.desktop", "/first/b.desktop", "Name=B"),
+            create_match("d.desktop", "/first/d.desktop", "Name=D"),
+        ];
+
+        let changes: Vec<(Change, &str)> = diff(&old, &new)
+            .into_iter()
+            .map(|(change, matched)| (change, matched.id.as_str()))
+            .collect();
+        assert_eq!(
+            changes,
+            vec![
+                (Change::Changed, "b.desktop"),
+                (Change::Added, "d.desktop"),
+                (Change::Removed, "c.desktop"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_wait() -> io::Result<()> {
+        let root = TempDir::new("watch");
+        let applications = root.join("applications");
+
+        let mut watcher = Watcher::new(std::slice::from_ref(&applications))?;
+        fs::create_dir_all(applications.join("vendor"))?;
+        watcher.wait(Duration::from_millis(10))?;
+
+        fs::write(applications.join("vendor/foo.desktop"), "Name=Foo\n")?;
+        watcher.wait(Duration::from_millis(10))?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,72 @@
+use std::env;
+use std::path::PathBuf;
+
+/// Returns the directory specified by the environment variable, or the fallback relative to the
+/// home directory if the variable is not set, empty or not an absolute path.
+fn base_dir(variable: &str, fallback: &str) -> Option<PathBuf> {
+    if let Ok(value) = env::var(variable) {
+        let path = PathBuf::from(value);
+        if path.is_absolute() {
+            return Some(path);
+        }
+    }
+
+    let home = env::var("HOME").ok()?;
+    let mut path = PathBuf::from(home);
+    path.push(fallback);
+    Some(path)
+}
+
+pub fn data_home() -> Option<PathBuf> {
+    base_dir("XDG_DATA_HOME", ".local/share")
+}
+
+pub fn config_home() -> Option<PathBuf> {
+    base_dir("XDG_CONFIG_HOME", ".config")
+}
+
+/// Returns $XDG_CONFIG_HOME followed by the directories specified by $XDG_CONFIG_DIRS, which
+/// defaults to /etc/xdg.
+pub fn config_dirs() -> Vec<PathBuf> {
+    let mut dirs: Vec<PathBuf> = config_home().into_iter().collect();
+    let value = env::var("XDG_CONFIG_DIRS").unwrap_or_default();
+    let mut system_dirs: Vec<PathBuf> = value
+        .split(':')
+        .map(PathBuf::from)
+        .filter(|dir| dir.is_absolute())
+        .collect();
+    if system_dirs.is_empty() {
+        system_dirs.push(PathBuf::from("/etc/xdg"));
+    }
+    dirs.extend(system_dirs);
+    dirs
+}
+
+/// Returns the names of the current desktop environments specified by $XDG_CURRENT_DESKTOP in the
+/// order of their precedence.
+pub fn current_desktops() -> Vec<String> {
+    env::var("XDG_CURRENT_DESKTOP")
+        .unwrap_or_default()
+        .split(':')
+        .filter(|desktop| !desktop.is_empty())
+        .map(String::from)
+        .collect()
+}
+
+pub fn state_home() -> Option<PathBuf> {
+    base_dir("XDG_STATE_HOME", ".local/state")
+}
+
+pub fn cache_home() -> Option<PathBuf> {
+    base_dir("XDG_CACHE_HOME", ".cache")
+}
+
+/// Returns $XDG_RUNTIME_DIR, which has no fallback.
+pub fn runtime_dir() -> Option<PathBuf> {
+    let path = PathBuf::from(env::var("XDG_RUNTIME_DIR").ok()?);
+    if path.is_absolute() {
+        Some(path)
+    } else {
+        None
+    }
+}